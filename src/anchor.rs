@@ -0,0 +1,281 @@
+use crate::*;
+use opencv::{objdetect, video};
+
+pub const KINDS: [&str; 3] = ["ArUco marker", "template", "optical flow"];
+
+/// pixels around the last template position that are searched
+const SEARCH: i32 = 40;
+/// normalized correlation below which the template counts as lost
+const MIN_CORRELATION: f64 = 0.6;
+/// tracked points below which the optical flow counts as lost
+const MIN_POINTS: usize = 4;
+const MAX_POINTS: i32 = 50;
+/// tracked points below which new corners are found in the roi
+const RESEED_POINTS: usize = 20;
+/// frames between reseeds of a roi that has too few corners
+const RESEED_WAIT: u32 = 30;
+
+#[derive(Clone, Debug)]
+pub enum Tracker {
+    /// id of an ArUco marker, followed through its center
+    Marker(i32),
+    /// grayscale patch matched around its last position
+    Template(Mat),
+    /// corners followed with pyramidal Lucas-Kanade, in frame coordinates
+    Flow(Vec<Point2f>),
+}
+
+/// attaches a roi to a tracked feature, the roi keeps its offset and size to it
+#[derive(Clone, Debug)]
+pub struct Anchor {
+    pub tracker: Tracker,
+    /// tracked position in frame coordinates, the roi moves with its changes
+    pub position: Point2f,
+    pub lost: bool,
+    /// frames left before the next reseed
+    wait: u32,
+}
+
+/// per frame state shared by all anchors
+#[derive(Default)]
+pub struct Tracking {
+    /// detects markers every frame while set, also needed to attach to one
+    pub detect_markers: bool,
+    /// id and center of the markers in the current frame
+    pub markers: Vec<(i32, Point2f)>,
+    corners: Vector<Vector<Point2f>>,
+    detector: Option<objdetect::ArucoDetector>,
+    gray: Mat,
+    previous: Mat,
+}
+
+fn to_point(p: Point2f) -> Point {
+    Point::new(p.x.round() as i32, p.y.round() as i32)
+}
+
+impl Tracking {
+    /// converts the frame and finds the markers, call once per frame before `Anchor::update`
+    pub fn next_frame(&mut self, frame: &Mat, needs_markers: bool) {
+        std::mem::swap(&mut self.previous, &mut self.gray);
+        imgproc::cvt_color_def(frame, &mut self.gray, imgproc::COLOR_BGR2GRAY).unwrap();
+
+        self.markers.clear();
+        self.corners.clear();
+        if !self.detect_markers && !needs_markers {
+            return;
+        }
+        let detector = self.detector.get_or_insert_with(|| {
+            let dictionary = objdetect::get_predefined_dictionary(
+                objdetect::PredefinedDictionaryType::DICT_4X4_50,
+            )
+            .unwrap();
+            objdetect::ArucoDetector::new(
+                &dictionary,
+                &objdetect::DetectorParameters::default().unwrap(),
+                objdetect::RefineParameters::new_def().unwrap(),
+            )
+            .unwrap()
+        });
+        let (mut ids, mut rejected) = (Vector::<i32>::new(), Vector::<Vector<Point2f>>::new());
+        detector
+            .detect_markers(&self.gray, &mut self.corners, &mut ids, &mut rejected)
+            .unwrap();
+        self.markers = ids
+            .iter()
+            .zip(self.corners.iter())
+            .map(|(id, corners)| {
+                let sum = corners.iter().fold(Point2f::default(), |a, b| a + b);
+                (id, sum * (1. / corners.len().max(1) as f32))
+            })
+            .collect();
+    }
+
+    /// marker id nearest to the point
+    pub fn nearest_marker(&self, point: Point) -> Option<i32> {
+        let point = Point2f::new(point.x as f32, point.y as f32);
+        self.markers
+            .iter()
+            .min_by(|a, b| (a.1 - point).norm().total_cmp(&(b.1 - point).norm()))
+            .map(|(id, _)| *id)
+    }
+
+    pub fn draw(&self, mat: &mut Mat) {
+        let cyan: Scalar = [255., 255., 0., 255.].into();
+        for ((id, center), corners) in self.markers.iter().zip(self.corners.iter()) {
+            let points: Vector<Point> = corners.iter().map(to_point).collect();
+            let polygons = Vector::<Vector<Point>>::from_iter([points]);
+            imgproc::polylines_def(mat, &polygons, true, cyan).unwrap();
+            let font = imgproc::FONT_HERSHEY_SIMPLEX;
+            imgproc::put_text_def(mat, &id.to_string(), to_point(*center), font, 0.4, cyan)
+                .unwrap();
+        }
+    }
+}
+
+impl Anchor {
+    /// starts tracking from the current frame of `tracking`, `kind` indexes `KINDS`
+    pub fn attach(kind: usize, roi: &roi::Roi, tracking: &Tracking) -> Option<Self> {
+        let gray = &tracking.gray;
+        if gray.empty() {
+            return None;
+        }
+        let (rect, mask) = roi.mask(gray.size().unwrap());
+        if rect.empty() {
+            return None;
+        }
+        let tl = Point2f::new(rect.x as f32, rect.y as f32);
+        let (tracker, position) = match kind {
+            0 => {
+                let center = rect.tl() + Point::new(rect.width / 2, rect.height / 2);
+                let id = tracking.nearest_marker(center)?;
+                let (_, position) = tracking.markers.iter().find(|m| m.0 == id)?;
+                (Tracker::Marker(id), *position)
+            }
+            1 => (
+                Tracker::Template(gray.roi(rect).unwrap().try_clone().unwrap()),
+                tl,
+            ),
+            _ => (Tracker::Flow(corners(gray, rect, &mask)?), tl),
+        };
+        Some(Self {
+            tracker,
+            position,
+            lost: false,
+            wait: 0,
+        })
+    }
+
+    /// follows the feature into the current frame, returns how far the roi moves
+    pub fn update(&mut self, tracking: &Tracking) -> Point {
+        let position = match self.tracker {
+            Tracker::Marker(id) => tracking
+                .markers
+                .iter()
+                .find(|m| m.0 == id)
+                .map(|(_, center)| *center),
+            Tracker::Template(ref template) => self.match_template(template, &tracking.gray),
+            Tracker::Flow(ref mut points) => {
+                flow(points, &tracking.previous, &tracking.gray).map(|d| self.position + d)
+            }
+        };
+        self.lost = position.is_none();
+        let Some(position) = position else {
+            return Point::default();
+        };
+        let delta = to_point(position) - to_point(self.position);
+        self.position = position;
+        delta
+    }
+
+    /// finds new corners in the roi once too few flow points survive a successful step.
+    /// A lost anchor is left alone, its roi no longer covers the feature.
+    pub fn reseed(&mut self, roi: &roi::Roi, tracking: &Tracking) {
+        let Tracker::Flow(ref mut points) = self.tracker else {
+            return;
+        };
+        if self.lost || points.len() >= RESEED_POINTS || tracking.gray.empty() {
+            return;
+        }
+        if self.wait > 0 {
+            self.wait -= 1;
+            return;
+        }
+        let (rect, mask) = roi.mask(tracking.gray.size().unwrap());
+        let found = match rect.empty() {
+            true => Vec::new(),
+            false => corners(&tracking.gray, rect, &mask).unwrap_or_default(),
+        };
+        if found.len() < RESEED_POINTS {
+            self.wait = RESEED_WAIT;
+        }
+        if found.len() > points.len() {
+            *points = found;
+        }
+    }
+
+    fn match_template(&self, template: &Mat, gray: &Mat) -> Option<Point2f> {
+        let size = gray.size().unwrap();
+        let (w, h) = (template.cols(), template.rows());
+        let search = Rect::new(
+            self.position.x as i32 - SEARCH,
+            self.position.y as i32 - SEARCH,
+            w + 2 * SEARCH,
+            h + 2 * SEARCH,
+        ) & Rect::new(0, 0, size.width, size.height);
+        if search.width < w || search.height < h {
+            return None;
+        }
+        let mut result = Mat::default();
+        imgproc::match_template_def(
+            &gray.roi(search).unwrap(),
+            template,
+            &mut result,
+            imgproc::TM_CCOEFF_NORMED,
+        )
+        .unwrap();
+        let (mut max, mut location) = (0., Point::default());
+        min_max_loc(
+            &result,
+            None,
+            Some(&mut max),
+            None,
+            Some(&mut location),
+            &no_array(),
+        )
+        .unwrap();
+        let tl = search.tl() + location;
+        (max >= MIN_CORRELATION).then(|| Point2f::new(tl.x as f32, tl.y as f32))
+    }
+}
+
+/// corners to follow inside the masked rect, in frame coordinates
+fn corners(gray: &Mat, rect: Rect, mask: &Mat) -> Option<Vec<Point2f>> {
+    let mut corners = Vector::<Point2f>::new();
+    imgproc::good_features_to_track(
+        &gray.roi(rect).unwrap(),
+        &mut corners,
+        MAX_POINTS,
+        0.01,
+        3.,
+        mask,
+        3,
+        false,
+        0.04,
+    )
+    .unwrap();
+    if corners.len() < MIN_POINTS {
+        return None;
+    }
+    let tl = Point2f::new(rect.x as f32, rect.y as f32);
+    Some(corners.iter().map(|p| p + tl).collect())
+}
+
+/// moves the points into the current frame and drops the ones not found, returns the mean motion
+fn flow(points: &mut Vec<Point2f>, previous: &Mat, gray: &Mat) -> Option<Point2f> {
+    if previous.empty() || previous.size().unwrap() != gray.size().unwrap() {
+        return None;
+    }
+    let before = Vector::<Point2f>::from_iter(points.iter().copied());
+    let (mut after, mut status, mut err) = (
+        Vector::<Point2f>::new(),
+        Vector::<u8>::new(),
+        Mat::default(),
+    );
+    video::calc_optical_flow_pyr_lk_def(previous, gray, &before, &mut after, &mut status, &mut err)
+        .unwrap();
+    let moved: Vec<(Point2f, Point2f)> = before
+        .iter()
+        .zip(after.iter())
+        .zip(status.iter())
+        .filter(|(_, s)| *s != 0)
+        .map(|(p, _)| p)
+        .collect();
+    *points = moved.iter().map(|(_, a)| *a).collect();
+    if moved.len() < MIN_POINTS {
+        return None;
+    }
+    let sum = moved
+        .iter()
+        .fold(Point2f::default(), |s, (b, a)| s + (*a - *b));
+    Some(sum * (1. / moved.len() as f32))
+}
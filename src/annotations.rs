@@ -0,0 +1,162 @@
+use crate::*;
+use std::io::{BufWriter, Write};
+
+fn bbox(rect: Rect) -> String {
+    format!("[{},{},{},{}]", rect.x, rect.y, rect.width, rect.height)
+}
+
+/// streams the shown detections of every frame to a JSON Lines file in the output folder
+#[derive(Default)]
+pub struct Log {
+    pub path: Option<String>,
+    pub rows: u64,
+    /// writes a COCO annotation file next to every saved snapshot
+    pub coco: bool,
+    writer: Option<BufWriter<fs::File>>,
+    error: Option<String>,
+}
+
+impl Log {
+    pub fn start(&mut self) -> std::io::Result<()> {
+        self.start_at(utils::get_save_filepath("detections.jsonl"))
+    }
+
+    pub fn start_at(&mut self, path: String) -> std::io::Result<()> {
+        self.writer = Some(BufWriter::new(fs::File::create(&path)?));
+        self.path = Some(path);
+        self.rows = 0;
+        Ok(())
+    }
+
+    pub fn running(&self) -> bool {
+        self.writer.is_some()
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            let _ = writer.flush();
+        }
+    }
+
+    /// one line for the detections of `frame`, under the time it was captured
+    pub fn log(
+        &mut self,
+        frame: u64,
+        timestamp: f64,
+        objects: &[detection::Object],
+        classes: &Classes,
+    ) {
+        let Some(ref mut writer) = self.writer else {
+            return;
+        };
+        let objects: Vec<String> = objects
+            .iter()
+            .map(|object| {
+                let track = object.track.map_or("null".into(), |t| t.to_string());
+                format!(
+                    "{{\"track\":{},\"class_id\":{},\"class\":{},\"score\":{},\"bbox\":{}}}",
+                    track,
+                    object.class,
+                    utils::json_string(&detection::label(classes, object.class)),
+                    utils::json_number(object.score as f64),
+                    bbox(object.rect)
+                )
+            })
+            .collect();
+        let result = writeln!(
+            writer,
+            "{{\"timestamp\":{:.6},\"frame\":{},\"detections\":[{}]}}",
+            timestamp,
+            frame,
+            objects.join(",")
+        );
+        match result {
+            Ok(()) => self.rows += 1,
+            Err(err) => {
+                self.error = Some(err.to_string());
+                self.stop();
+            }
+        }
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui) {
+        ui.text("detection log:");
+        ui.same_line();
+        match self.writer {
+            Some(_) => {
+                if ui.button("stop##detection log") {
+                    self.stop();
+                }
+                ui.same_line();
+                let path = self.path.as_deref().unwrap_or_default();
+                ui.text(format!("{} frames to {}", self.rows, path));
+            }
+            None => {
+                if ui.button("start##detection log") {
+                    self.error = self.start().err().map(|err| err.to_string());
+                }
+            }
+        }
+        ui.same_line();
+        ui.checkbox("COCO with snapshots", &mut self.coco);
+        if let Some(ref error) = self.error {
+            ui.text(format!("detection log error: {}", error));
+        }
+    }
+}
+
+impl Drop for Log {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// writes the objects as a COCO annotation file for one image, next to it with a .json extension
+pub fn write_coco(
+    image: &str,
+    size: Size,
+    objects: &[detection::Object],
+    classes: &Classes,
+) -> std::io::Result<String> {
+    let image_path = path::Path::new(image);
+    let path = image_path.with_extension("json");
+    let file_name = image_path.file_name().unwrap_or_default().to_string_lossy();
+
+    let annotations: Vec<String> = objects
+        .iter()
+        .enumerate()
+        .map(|(n, object)| {
+            let track = object.track.map_or("null".into(), |t| t.to_string());
+            format!(
+                "{{\"id\":{},\"image_id\":1,\"category_id\":{},\"bbox\":{},\"area\":{},\
+                 \"iscrowd\":0,\"score\":{},\"track_id\":{}}}",
+                n + 1,
+                object.class,
+                bbox(object.rect),
+                object.rect.area(),
+                utils::json_number(object.score as f64),
+                track
+            )
+        })
+        .collect();
+    let categories: Vec<String> = classes
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| !name.is_empty())
+        .map(|(n, name)| format!("{{\"id\":{},\"name\":{}}}", n, utils::json_string(name)))
+        .collect();
+
+    let mut writer = BufWriter::new(fs::File::create(&path)?);
+    writeln!(
+        writer,
+        "{{\"images\":[{{\"id\":1,\"file_name\":{},\"width\":{},\"height\":{}}}],\
+         \"annotations\":[{}],\"categories\":[{}]}}",
+        utils::json_string(&file_name),
+        size.width,
+        size.height,
+        annotations.join(","),
+        categories.join(",")
+    )?;
+    writer.flush()?;
+    Ok(path.to_string_lossy().into_owned())
+}
@@ -0,0 +1,107 @@
+use crate::*;
+
+/// extensions read from an image folder
+const IMAGE_EXTENSIONS: [&str; 6] = ["png", "jpg", "jpeg", "bmp", "tif", "tiff"];
+
+/// frames of a recorded video, or the images of a folder in name order
+pub enum Source {
+    Video(videoio::VideoCapture),
+    Images(std::vec::IntoIter<path::PathBuf>),
+}
+
+impl Source {
+    pub fn open(input: &str) -> Result<Self, String> {
+        let input_path = path::Path::new(input);
+        if input_path.is_dir() {
+            let dir = fs::read_dir(input_path).map_err(|err| format!("{}: {}", input, err))?;
+            let mut images: Vec<path::PathBuf> = dir
+                .filter_map(|item| Some(item.ok()?.path()))
+                .filter(|path| {
+                    let extension = path.extension().and_then(|e| e.to_str());
+                    extension.is_some_and(|e| IMAGE_EXTENSIONS.contains(&&*e.to_lowercase()))
+                })
+                .collect();
+            if images.is_empty() {
+                return Err(format!("no images in {}", input));
+            }
+            images.sort();
+            return Ok(Self::Images(images.into_iter()));
+        }
+        let capture = videoio::VideoCapture::from_file_def(input)
+            .map_err(|err| format!("{}: {}", input, err.message))?;
+        if !capture.is_opened().unwrap_or(false) {
+            return Err(format!("could not open {}", input));
+        }
+        Ok(Self::Video(capture))
+    }
+
+    /// the next frame and a name for its outputs, None at the end
+    fn next(&mut self, frame: u64) -> Option<Result<(Mat, String), String>> {
+        match self {
+            Self::Video(capture) => {
+                let mut mat = Mat::default();
+                match capture.read(&mut mat) {
+                    Ok(true) if !mat.empty() => Some(Ok((mat, format!("{:06}", frame)))),
+                    Ok(_) => None,
+                    Err(err) => Some(Err(err.message)),
+                }
+            }
+            Self::Images(images) => {
+                let path = images.next()?;
+                // the extension is kept, so clip.png and clip.jpg get outputs of their own
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                let name = name.replace('.', "_");
+                let mat = imgcodecs::imread_def(&path.to_string_lossy());
+                Some(match mat {
+                    Ok(mat) if !mat.empty() => Ok((mat, name)),
+                    Ok(_) => Err(format!("could not read {}", path.display())),
+                    Err(err) => Err(err.message),
+                })
+            }
+        }
+    }
+}
+
+/// runs the detector over every frame of `input`, writing the annotated frames,
+/// a detections.jsonl log and optionally COCO files to `output`. Returns the frame count.
+pub fn run(detector: &mut Detector, input: &str, output: &str, coco: bool) -> Result<u64, String> {
+    let mut source = Source::open(input)?;
+    fs::create_dir_all(output).map_err(|err| format!("{}: {}", output, err))?;
+    let (backend, classes) = detector.files.load()?;
+    let mut models = tiling::Models::new(backend, detector.files.clone());
+    detector.classes = classes;
+    let output = path::Path::new(output);
+    let log_path = output.join("detections.jsonl");
+    detector
+        .log
+        .start_at(log_path.to_string_lossy().into_owned())
+        .map_err(|err| format!("{}: {}", log_path.display(), err))?;
+
+    let mut frame = 0;
+    while let Some(next) = source.next(frame + 1) {
+        let (mut mat, name) = next?;
+        let timestamp = utils::timestamp();
+        frame += 1;
+        let detections = tiling::detect(&mut models, &mat, &detector.input)
+            .map_err(|err| format!("{} on {}", err, name))?;
+        detector.receive(frame, timestamp, detections);
+        detector.objects = detector.shown();
+
+        let size = mat.size().unwrap();
+        detector.draw_boxes(&mut mat);
+        let image = output
+            .join(format!("{}.png", name))
+            .to_string_lossy()
+            .into_owned();
+        imgcodecs::imwrite_def(&image, &mat).map_err(|err| err.message)?;
+        if coco {
+            annotations::write_coco(&image, size, &detector.objects, &detector.classes)
+                .map_err(|err| format!("{}: {}", image, err))?;
+        }
+        if frame % 100 == 0 {
+            eprintln!("{} frames", frame);
+        }
+    }
+    detector.log.stop();
+    Ok(frame)
+}
@@ -0,0 +1,162 @@
+use crate::*;
+
+/// display settings of one class
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClassStyle {
+    pub shown: bool,
+    /// RGB in 0..1, as edited in the panel
+    pub color: [f32; 3],
+    /// replaces the panel confidence threshold for this class
+    pub min_confidence: Option<f32>,
+}
+
+impl ClassStyle {
+    /// shown, with a hue spread by the golden ratio so neighbouring ids differ
+    fn new(class: ClassID) -> Self {
+        let hue = (class as f32 * 0.618_034).fract() * 6.;
+        let x = 1. - (hue % 2. - 1.).abs();
+        let color = match hue as i32 {
+            0 => [1., x, 0.],
+            1 => [x, 1., 0.],
+            2 => [0., 1., x],
+            3 => [0., x, 1.],
+            4 => [x, 0., 1.],
+            _ => [1., 0., x],
+        };
+        Self {
+            shown: true,
+            color,
+            min_confidence: None,
+        }
+    }
+
+    /// BGR for drawing on the feeds
+    pub fn scalar(&self) -> Scalar {
+        let [r, g, b] = self.color.map(|c| c as f64 * 255.);
+        [b, g, r, 255.].into()
+    }
+}
+
+/// which classes are drawn, tracked and logged, and how they are drawn
+#[derive(Default)]
+pub struct ClassFilter {
+    /// indexed by class id, grown as ids show up
+    pub styles: Vec<ClassStyle>,
+    search: String,
+}
+
+impl ClassFilter {
+    pub fn style(&self, class: ClassID) -> ClassStyle {
+        match self.styles.get(class as usize) {
+            Some(style) => *style,
+            None => ClassStyle::new(class),
+        }
+    }
+
+    fn style_mut(&mut self, class: ClassID) -> &mut ClassStyle {
+        let n = class.max(0) as usize;
+        while self.styles.len() <= n {
+            let new = ClassStyle::new(self.styles.len() as ClassID);
+            self.styles.push(new);
+        }
+        &mut self.styles[n]
+    }
+
+    /// lowest threshold any class uses, to run non-maximum suppression with
+    pub fn lowest_confidence(&self, confidence: f32) -> f32 {
+        let overrides = self.styles.iter().filter_map(|s| s.min_confidence);
+        overrides.fold(confidence, f32::min)
+    }
+
+    /// the class is selected and the score reaches its threshold
+    pub fn keeps(&self, class: ClassID, score: Score, confidence: f32) -> bool {
+        let style = self.style(class);
+        style.shown && score >= style.min_confidence.unwrap_or(confidence)
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui, classes: &Classes, confidence: f32) {
+        if classes.is_empty() {
+            return;
+        }
+        let Some(_node) = ui.tree_node("classes") else {
+            return;
+        };
+        ui.input_text("search##classes", &mut self.search).build();
+        let search = self.search.to_lowercase();
+        let matching: Vec<ClassID> = (0..classes.len() as ClassID)
+            .filter(|&n| !classes[n as usize].is_empty())
+            .filter(|&n| classes[n as usize].to_lowercase().contains(&search))
+            .collect();
+        for (name, shown) in [("show all", true), ("hide all", false)] {
+            if ui.button(format!("{}##classes", name)) {
+                for &class in &matching {
+                    self.style_mut(class).shown = shown;
+                }
+            }
+            ui.same_line();
+        }
+        ui.new_line();
+        let Some(_child) = ui.child_window("class list").size([0., 200.]).begin() else {
+            return;
+        };
+        for class in matching {
+            let _id = ui.push_id_int(class);
+            let style = self.style_mut(class);
+            ui.color_edit3_config("##color", &mut style.color)
+                .inputs(false)
+                .label(false)
+                .build();
+            ui.same_line();
+            ui.checkbox(&classes[class as usize], &mut style.shown);
+            ui.same_line();
+            let mut own = style.min_confidence.is_some();
+            if ui.checkbox("own threshold", &mut own) {
+                style.min_confidence = own.then_some(confidence);
+            }
+            if let Some(ref mut min_confidence) = style.min_confidence {
+                ui.same_line();
+                ui.set_next_item_width(120.);
+                ui.slider("##min confidence", 0.05, 1., min_confidence);
+            }
+        }
+    }
+}
+
+impl Persist for ClassFilter {
+    /// only the classes that differ from their default, as `class_<id>`
+    fn save(&self, storage: &mut FileStorage) -> Result<()> {
+        for (n, style) in self.styles.iter().enumerate() {
+            if *style == ClassStyle::new(n as ClassID) {
+                continue;
+            }
+            storage.start_write_struct_def(&format!("class_{}", n), FileNode_MAP)?;
+            storage.write_i32("shown", style.shown as i32)?;
+            for (key, value) in ["red", "green", "blue"].iter().zip(style.color) {
+                storage.write_f64(key, value as f64)?;
+            }
+            storage.write_f64("min_confidence", style.min_confidence.unwrap_or(-1.) as f64)?;
+            storage.end_write_struct()?;
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, node: &FileNode) -> Result<()> {
+        for key in node.keys()? {
+            let Some(class) = key.strip_prefix("class_").and_then(|n| n.parse().ok()) else {
+                continue;
+            };
+            let node = node.get(&key)?;
+            let style = self.style_mut(class);
+            profile::read_bool(&node, "shown", &mut style.shown)?;
+            for (key, value) in ["red", "green", "blue"].iter().zip(&mut style.color) {
+                let mut channel = *value as f64;
+                profile::read_f64(&node, key, &mut channel)?;
+                *value = channel as f32;
+            }
+            let mut min_confidence = style.min_confidence.unwrap_or(-1.) as f64;
+            profile::read_f64(&node, "min_confidence", &mut min_confidence)?;
+            style.min_confidence = (min_confidence >= 0.).then_some(min_confidence as f32);
+        }
+        Ok(())
+    }
+}
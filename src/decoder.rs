@@ -0,0 +1,183 @@
+use crate::*;
+
+/// model families the detection thread can run, index of `ModelFiles::family`
+pub const FAMILIES: [&str; 4] = [
+    "Darknet YOLOv3",
+    "ONNX YOLOv5",
+    "ONNX YOLOv8",
+    "ONNX YOLOv10",
+];
+/// scores below this are dropped by the backends, the detection panel thresholds apply later
+pub const CONFIDENCE: f32 = 0.05;
+/// gray of the letterbox padding, as in the ultralytics preprocessing
+const PAD: f64 = 114.;
+
+/// scale and padding of a frame fitted into the square network input
+#[derive(Clone, Copy, Debug)]
+pub struct Letterbox {
+    pub scale: f32,
+    pub pad: [f32; 2],
+}
+
+impl Letterbox {
+    /// resizes the frame keeping its aspect ratio and pads it to `input` x `input`
+    pub fn apply(frame: &Mat, input: i32) -> (Mat, Self) {
+        let size = frame.size().unwrap();
+        let scale = (input as f32 / size.width as f32).min(input as f32 / size.height as f32);
+        let resized_size = Size::new(
+            (size.width as f32 * scale).round() as i32,
+            (size.height as f32 * scale).round() as i32,
+        );
+        let mut resized = Mat::default();
+        imgproc::resize_def(frame, &mut resized, resized_size).unwrap();
+        let (left, top) = (
+            (input - resized_size.width) / 2,
+            (input - resized_size.height) / 2,
+        );
+        let mut padded = Mat::default();
+        copy_make_border(
+            &resized,
+            &mut padded,
+            top,
+            input - resized_size.height - top,
+            left,
+            input - resized_size.width - left,
+            BORDER_CONSTANT,
+            Scalar::all(PAD),
+        )
+        .unwrap();
+        let letterbox = Self {
+            scale,
+            pad: [left as f32, top as f32],
+        };
+        (padded, letterbox)
+    }
+
+    /// maps corners in network input coordinates back to the frame
+    pub fn to_frame(&self, x1: f32, y1: f32, x2: f32, y2: f32) -> Rect {
+        let x = |v: f32| ((v - self.pad[0]) / self.scale).round() as i32;
+        let y = |v: f32| ((v - self.pad[1]) / self.scale).round() as i32;
+        Rect::from_points(Point::new(x(x1), y(y1)), Point::new(x(x2), y(y2)))
+    }
+}
+
+/// turns the raw output tensor of a model family into detections in frame coordinates
+pub trait Decoder: Send {
+    fn decode(&self, output: &Mat, letterbox: &Letterbox) -> Detections;
+}
+
+/// rows of `[cx, cy, w, h, objectness, class scores..]`, shape (1, boxes, 5 + classes)
+pub struct YoloV5;
+/// columns of `[cx, cy, w, h, class scores..]`, shape (1, 4 + classes, boxes)
+pub struct YoloV8;
+/// rows of `[x1, y1, x2, y2, score, class]` after the model's own NMS, shape (1, boxes, 6)
+pub struct YoloV10;
+
+pub fn decoder(family: usize) -> Box<dyn Decoder> {
+    match family {
+        1 => Box::new(YoloV5),
+        2 => Box::new(YoloV8),
+        _ => Box::new(YoloV10),
+    }
+}
+
+/// the two trailing dimensions and the data of a (1, a, b) f32 output
+fn tensor(output: &Mat) -> Option<(usize, usize, &[f32])> {
+    let dims = output.mat_size();
+    if dims.len() != 3 || output.depth() != CV_32F {
+        return None;
+    }
+    let data = output.data_typed::<f32>().ok()?;
+    Some((dims[1] as usize, dims[2] as usize, data))
+}
+
+/// index and value of the best class score
+fn best_class(scores: impl Iterator<Item = f32>) -> (usize, f32) {
+    scores.enumerate().fold(
+        (0, f32::MIN),
+        |best, (n, s)| if s > best.1 { (n, s) } else { best },
+    )
+}
+
+fn push(detections: &mut Detections, class: usize, score: f32, rect: Rect) {
+    let (class_ids, scores, rects) = detections;
+    class_ids.push(class as ClassID);
+    scores.push(score);
+    rects.push(rect);
+}
+
+/// center, width and height to corners
+fn corners(cx: f32, cy: f32, w: f32, h: f32) -> [f32; 4] {
+    [cx - w / 2., cy - h / 2., cx + w / 2., cy + h / 2.]
+}
+
+impl Decoder for YoloV5 {
+    fn decode(&self, output: &Mat, letterbox: &Letterbox) -> Detections {
+        let mut detections = Detections::default();
+        let Some((boxes, width, data)) = tensor(output) else {
+            return detections;
+        };
+        if width < 6 {
+            return detections;
+        }
+        for row in data.chunks_exact(width).take(boxes) {
+            let (class, class_score) = best_class(row[5..].iter().copied());
+            let score = row[4] * class_score;
+            if score >= CONFIDENCE {
+                let [x1, y1, x2, y2] = corners(row[0], row[1], row[2], row[3]);
+                push(
+                    &mut detections,
+                    class,
+                    score,
+                    letterbox.to_frame(x1, y1, x2, y2),
+                );
+            }
+        }
+        detections
+    }
+}
+
+impl Decoder for YoloV8 {
+    fn decode(&self, output: &Mat, letterbox: &Letterbox) -> Detections {
+        let mut detections = Detections::default();
+        let Some((height, boxes, data)) = tensor(output) else {
+            return detections;
+        };
+        if height < 5 {
+            return detections;
+        }
+        let at = |row: usize, n: usize| data[row * boxes + n];
+        for n in 0..boxes {
+            let (class, score) = best_class((4..height).map(|row| at(row, n)));
+            if score >= CONFIDENCE {
+                let [x1, y1, x2, y2] = corners(at(0, n), at(1, n), at(2, n), at(3, n));
+                push(
+                    &mut detections,
+                    class,
+                    score,
+                    letterbox.to_frame(x1, y1, x2, y2),
+                );
+            }
+        }
+        detections
+    }
+}
+
+impl Decoder for YoloV10 {
+    fn decode(&self, output: &Mat, letterbox: &Letterbox) -> Detections {
+        let mut detections = Detections::default();
+        let Some((boxes, width, data)) = tensor(output) else {
+            return detections;
+        };
+        if width < 6 {
+            return detections;
+        }
+        for row in data.chunks_exact(width).take(boxes) {
+            if row[4] >= CONFIDENCE {
+                let rect = letterbox.to_frame(row[0], row[1], row[2], row[3]);
+                push(&mut detections, row[5] as usize, row[4], rect);
+            }
+        }
+        detections
+    }
+}
@@ -1,103 +1,616 @@
+use crate::profile::{self, Persist};
 use crate::*;
-use mpsc::{Receiver, Sender};
+use mpsc::Sender;
 
-const WEIGHTS_FILE: &str = "data/yolov3.weights";
-const CONFIG_FILE: &str = "data/yolov3.cfg";
-const CLASSES_FILE: &str = "data/yolov3.txt";
+pub const DEFAULT_WEIGHTS: &str = "data/yolov3.weights";
+pub const DEFAULT_CONFIG: &str = "data/yolov3.cfg";
+pub const DEFAULT_CLASSES: &str = "data/yolov3.txt";
+/// feeds the detections can run on
+pub const SOURCES: [&str; 3] = ["left", "right", "DoLP"];
+/// feed windows the detections can be drawn on
+pub const TARGETS: [&str; 5] = ["left", "right", "subtracted", "DoLP", "AoLP"];
 
 pub type Classes = Vec<String>;
 pub type ClassID = i32;
 pub type Score = f32;
 pub type Detections = (Vector<ClassID>, Vector<Score>, Vector<Rect>);
 
-pub type Channel<Here, There> = (Sender<Here>, Receiver<There>);
-pub trait ChannelTrait<Here, There> {
-    fn send_on_receive<SendFn: FnMut(There) -> Here>(&self, send_fn: SendFn) -> Result<(), &str>;
-}
-impl<Here, There> ChannelTrait<Here, There> for Channel<Here, There> {
-    fn send_on_receive<SendFn: FnMut(There) -> Here>(
-        &self,
-        mut send_fn: SendFn,
-    ) -> Result<(), &str> {
-        let (send_from_here, receive_from_there) = self;
-        let body = match receive_from_there.try_recv() {
-            Ok(body) => body,
-            _ => return Err("no body yet"),
+/// network input of the backends
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Input {
+    /// side of the square input
+    pub size: i32,
+    /// factor from 8-bit pixels to network values
+    pub scale: f64,
+    pub tiling: tiling::Tiling,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self {
+            size: 640,
+            scale: 1. / 255.,
+            tiling: Default::default(),
+        }
+    }
+}
+
+/// messages from the control panel to the detection thread
+pub enum Command {
+    /// the files it was loaded from, for copies to run tiles in parallel
+    Backend(Box<dyn Backend>, ModelFiles),
+    Input(Input),
+}
+
+/// runs a loaded model on a frame
+pub trait Backend: Send {
+    fn detect(&mut self, feed: &Mat, input: &Input) -> Result<Detections>;
+}
+
+/// Darknet weights and config through `dnn::DetectionModel`
+pub struct Darknet {
+    model: dnn::DetectionModel,
+    /// input the model was last set up with
+    input: Option<Input>,
+}
+
+impl Backend for Darknet {
+    fn detect(&mut self, feed: &Mat, input: &Input) -> Result<Detections> {
+        let model = &mut self.model;
+        if self.input != Some(*input) {
+            model.set_input_size(Size::new(input.size, input.size))?;
+            model.set_input_scale(input.scale.into())?;
+            model.set_input_mean([0.; 4].into())?;
+            model.set_input_swap_rb(true)?;
+            self.input = Some(*input);
+        }
+        let mut detections = Detections::default();
+        let (ref mut class_ids, ref mut scores, ref mut rects) = &mut detections;
+        // every candidate above the prefilter, the panel thresholds and NMS run later
+        let confidence = decoder::CONFIDENCE;
+        model.detect(feed, class_ids, scores, rects, confidence, 0.)?;
+        Ok(detections)
+    }
+}
+
+/// an ONNX network fed letterboxed frames, its output read by the decoder of its family
+pub struct Onnx {
+    net: dnn::Net,
+    decoder: Box<dyn decoder::Decoder>,
+}
+
+impl Backend for Onnx {
+    fn detect(&mut self, feed: &Mat, input: &Input) -> Result<Detections> {
+        let (letterboxed, letterbox) = decoder::Letterbox::apply(feed, input.size);
+        let blob = dnn::blob_from_image(
+            &letterboxed,
+            input.scale,
+            Size::new(input.size, input.size),
+            Scalar::all(0.),
+            true,
+            false,
+            CV_32F,
+        )?;
+        self.net.set_input_def(&blob)?;
+        let output = self.net.forward_single_def()?;
+        Ok(self.decoder.decode(&output, &letterbox))
+    }
+}
+
+/// weights, network config and class names of the detection model
+#[derive(Clone, Debug)]
+pub struct ModelFiles {
+    /// index into `decoder::FAMILIES`
+    pub family: usize,
+    pub weights: String,
+    /// Darknet only
+    pub config: String,
+    pub classes: String,
+}
+
+impl Default for ModelFiles {
+    fn default() -> Self {
+        Self {
+            family: 0,
+            weights: DEFAULT_WEIGHTS.into(),
+            config: DEFAULT_CONFIG.into(),
+            classes: DEFAULT_CLASSES.into(),
+        }
+    }
+}
+
+impl ModelFiles {
+    /// error naming every missing file
+    pub fn validate(&self) -> Result<(), String> {
+        let missing: Vec<String> = [
+            ("weights", &self.weights),
+            ("config", &self.config),
+            ("classes", &self.classes),
+        ]
+        .iter()
+        .filter(|(name, _)| *name != "config" || self.family == 0)
+        .filter(|(_, file)| !path::Path::new(file).is_file())
+        .map(|(name, file)| format!("{} file {} not found", name, file))
+        .collect();
+        match missing.is_empty() {
+            true => Ok(()),
+            false => Err(missing.join(", ")),
+        }
+    }
+
+    pub fn load(&self) -> Result<(Box<dyn Backend>, Classes), String> {
+        self.validate()?;
+        let classes = fs::read_to_string(&self.classes)
+            .map_err(|err| format!("could not read {}: {}", self.classes, err))?
+            .lines()
+            .map(|x| x.to_string())
+            .collect();
+        let error = |err: opencv::Error| format!("could not load model: {}", err.message);
+        let backend: Box<dyn Backend> = match self.family {
+            0 => Box::new(Darknet {
+                model: dnn::DetectionModel::new(&self.weights, &self.config).map_err(error)?,
+                input: None,
+            }),
+            family => Box::new(Onnx {
+                net: dnn::read_net_from_onnx(&self.weights).map_err(error)?,
+                decoder: decoder::decoder(family),
+            }),
         };
-        let data = send_fn(body);
-        let _ = send_from_here.send(data);
-        Ok(())
+        Ok((backend, classes))
     }
 }
 
-/// Here and There are relative to its creation!
-/// Think of these as "data from here" and "data from there"
-pub struct Channels<Here, There> {
-    pub channel_here: Channel<Here, There>,
-    pub channel_there: Channel<There, Here>,
-    pub body: Option<There>,
-    pub first_sent: bool,
+impl Persist for ModelFiles {
+    fn save(&self, storage: &mut FileStorage) -> Result<()> {
+        storage.write_i32("family", self.family as i32)?;
+        storage.write_str("weights", &self.weights)?;
+        storage.write_str("config", &self.config)?;
+        storage.write_str("classes", &self.classes)
+    }
+
+    fn load(&mut self, node: &FileNode) -> Result<()> {
+        let mut family = self.family as i32;
+        profile::read_i32(node, "family", &mut family)?;
+        self.family = (family.max(0) as usize).min(decoder::FAMILIES.len() - 1);
+        profile::read_string(node, "weights", &mut self.weights)?;
+        profile::read_string(node, "config", &mut self.config)?;
+        profile::read_string(node, "classes", &mut self.classes)
+    }
+}
+
+/// polarimetric signature of one shown detection or track
+#[derive(Clone, Debug, Default)]
+pub struct Object {
+    pub track: Option<u64>,
+    pub class: ClassID,
+    pub label: String,
+    pub score: Score,
+    pub rect: Rect,
+    pub polar: Option<stats::PolarStats>,
+    /// per BGR channel of the subtracted feed
+    pub difference: [stats::ChannelStats; 3],
+}
+
+/// the model files and settings in use, and the detection thread running them
+pub struct Detector {
+    pub files: ModelFiles,
+    pub classes: Classes,
+    pub status: String,
+    /// index into `SOURCES`
+    pub source: usize,
+    /// in the order of `TARGETS`
+    pub targets: [bool; 5],
+    pub input: Input,
+    pub confidence: f32,
+    /// overlap above which the weaker of two boxes is suppressed
+    pub nms: f32,
+    pub class_filter: class_filter::ClassFilter,
+    /// latest result of the thread
+    pub detections: Option<Detections>,
+    pub tracker: tracker::Tracker,
+    /// what is drawn, measured in the current frame
+    pub objects: Vec<Object>,
+    pub log: annotations::Log,
+    /// frames in, detections out, both tagged with the frame number and its capture time
+    worker: Option<worker::Worker<(u64, f64, Mat), (u64, f64, Detections)>>,
+    /// commands to the running thread
+    commands: Option<Sender<Command>>,
 }
 
-impl<S, T> Channels<S, T> {
-    pub fn new() -> Self {
-        let (s1, r1) = mpsc::channel();
-        let (s2, r2) = mpsc::channel();
-        Channels {
-            channel_here: (s1, r2),
-            channel_there: (s2, r1),
-            body: None,
-            first_sent: false,
+impl Default for Detector {
+    fn default() -> Self {
+        Self {
+            files: Default::default(),
+            classes: Classes::new(),
+            status: String::new(),
+            source: 0,
+            targets: [false, false, true, false, false],
+            input: Default::default(),
+            confidence: 0.5,
+            nms: 0.1,
+            class_filter: Default::default(),
+            detections: None,
+            tracker: Default::default(),
+            objects: Vec::new(),
+            log: Default::default(),
+            worker: None,
+            commands: None,
         }
     }
 }
 
-pub fn initialize_thread(channel: Channel<Detections, Mat>) {
-    thread::spawn(move || {
-        let mut model = dnn::DetectionModel::new(WEIGHTS_FILE, CONFIG_FILE).unwrap();
-        let mut init = false;
-        loop {
-            let _ = channel.send_on_receive(|feed| {
-                if !init {
-                    init = true;
-                    model.set_input_size(feed.size().unwrap()).unwrap();
-                    model.set_input_scale(0.001.into()).unwrap();
-                    model.set_input_mean([0.; 4].into()).unwrap();
-                    model.set_input_swap_rb(true).unwrap();
+impl Detector {
+    /// loads the model files and starts the detection thread
+    pub fn start(&mut self) -> Result<(), String> {
+        let (backend, classes) = self.files.load()?;
+        let (sender, commands) = mpsc::channel();
+        let (mut models, mut input) =
+            (tiling::Models::new(backend, self.files.clone()), self.input);
+        self.worker = Some(worker::Worker::spawn(
+            move |(frame, timestamp, feed): (u64, f64, Mat)| {
+                for command in commands.try_iter() {
+                    match command {
+                        Command::Backend(backend, files) => {
+                            models = tiling::Models::new(backend, files);
+                        }
+                        Command::Input(new_input) => input = new_input,
+                    }
                 }
-                let mut detections = Detections::default();
-                let (ref mut class_ids, ref mut scores, ref mut rects) = &mut detections;
-                let _ = model.detect_def(&feed, class_ids, scores, rects);
-                detections
+                if feed.empty() {
+                    return Ok((frame, timestamp, Detections::default()));
+                }
+                tiling::detect(&mut models, &feed, &input).map(|d| (frame, timestamp, d))
+            },
+        ));
+        self.classes = classes;
+        self.commands = Some(sender);
+        Ok(())
+    }
+
+    /// collects the latest detections and hands the feed of `frame` to the thread
+    pub fn update(&mut self, feed: &Mat, frame: u64) {
+        let Some(ref mut worker) = self.worker else {
+            return;
+        };
+        let latest = worker.latest();
+        worker.submit((frame, utils::timestamp(), feed.clone()));
+        match latest {
+            Some(Ok((taken, timestamp, detections))) => self.receive(taken, timestamp, detections),
+            Some(Err(err)) => self.status = err,
+            None => {}
+        }
+    }
+
+    /// takes the detections made on `frame`, captured at `timestamp`, as the latest result
+    pub fn receive(&mut self, frame: u64, timestamp: f64, detections: Detections) {
+        if self.tracker.enabled {
+            let filtered = self.filter(&detections);
+            self.tracker.update(frame, &filtered);
+        }
+        self.detections = Some(detections);
+        // once per result, under the frame it was taken from
+        if self.log.running() {
+            let shown = self.shown();
+            self.log.log(frame, timestamp, &shown, &self.classes);
+        }
+    }
+
+    /// detections of the selected classes above their thresholds, after non-maximum suppression
+    fn filter(&self, detections: &Detections) -> Vec<tracker::Detection> {
+        let confidence = self.class_filter.lowest_confidence(self.confidence);
+        let mut filtered = filter(detections, confidence, self.nms);
+        filtered.retain(|d| self.class_filter.keeps(d.class, d.score, self.confidence));
+        filtered
+    }
+
+    /// confirmed tracks while tracking, otherwise the filtered detections
+    pub fn shown(&self) -> Vec<Object> {
+        if self.tracker.enabled {
+            return self
+                .tracker
+                .tracks
+                .iter()
+                .filter(|t| t.confirmed() && self.class_filter.style(t.class).shown)
+                .map(|t| Object {
+                    track: Some(t.id),
+                    class: t.class,
+                    label: format!("#{} {}", t.id, label(&self.classes, t.class)),
+                    score: t.score,
+                    rect: t.rect,
+                    ..Default::default()
+                })
+                .collect();
+        }
+        let Some(ref detections) = self.detections else {
+            return Vec::new();
+        };
+        self.filter(detections)
+            .iter()
+            .map(|d| Object {
+                class: d.class,
+                label: label(&self.classes, d.class),
+                score: d.score,
+                rect: d.rect,
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// polarization and difference statistics inside every shown box
+    pub fn measure(&mut self, difference: &Mat, polarimetry: &polarimetry::Polarimetry) {
+        let size = difference.size().unwrap();
+        let frame = Rect::new(0, 0, size.width, size.height);
+        let has_polar = !polarimetry.dolp.empty() && polarimetry.dolp.size().unwrap() == size;
+        self.objects = self.shown();
+        for object in &mut self.objects {
+            let inside = object.rect & frame;
+            if inside.empty() || difference.empty() {
+                continue;
+            }
+            let mask =
+                Mat::new_size_with_default(inside.size(), CV_8UC1, Scalar::all(255.)).unwrap();
+            object.difference = stats::channels(&difference.roi(inside).unwrap(), &mask);
+            object.polar = has_polar.then(|| {
+                let dolp = polarimetry.dolp.roi(inside).unwrap();
+                let aolp = polarimetry.aolp.roi(inside).unwrap();
+                stats::polar(&dolp, &aolp, &mask)
             });
         }
-    });
+    }
+
+    fn send(&self, command: Command) -> Result<(), String> {
+        let Some(ref commands) = self.commands else {
+            return Err("detection is off, load a model to start it".into());
+        };
+        commands
+            .send(command)
+            .map_err(|_| "the detection thread has stopped".to_string())
+    }
+
+    pub fn running(&self) -> bool {
+        self.worker.is_some()
+    }
+
+    /// swaps the running model for the one in the current files, starts detection if it is off
+    pub fn reload(&mut self) -> Result<(), String> {
+        if !self.running() {
+            return self.start();
+        }
+        let (backend, classes) = self.files.load()?;
+        self.send(Command::Backend(backend, self.files.clone()))?;
+        self.classes = classes;
+        Ok(())
+    }
+
+    /// hands newly loaded settings to the running thread, a no-op before `start`
+    pub fn sync(&mut self) {
+        if self.commands.is_none() {
+            return;
+        }
+        let synced = self
+            .send(Command::Input(self.input))
+            .and_then(|()| self.reload());
+        self.status = match synced {
+            Ok(()) => format!("loaded {}", self.files.weights),
+            Err(err) => err,
+        };
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui) {
+        ui.text("detection model:");
+        let files = &mut self.files;
+        ui.combo_simple_string("model family", &mut files.family, &decoder::FAMILIES);
+        ui.input_text("weights", &mut files.weights).build();
+        if files.family == 0 {
+            ui.input_text("config", &mut files.config).build();
+        }
+        ui.input_text("classes", &mut files.classes).build();
+        if ui.button("load model") {
+            self.status = match self.reload() {
+                Ok(()) => format!("loaded {}", self.files.weights),
+                Err(err) => err,
+            };
+        }
+        if !self.status.is_empty() {
+            ui.same_line();
+            ui.text(&self.status);
+        }
+
+        ui.combo_simple_string("detect on", &mut self.source, &SOURCES);
+        ui.text("draw on:");
+        for (n, name) in TARGETS.iter().enumerate() {
+            ui.same_line();
+            ui.checkbox(format!("{}##detection target", name), &mut self.targets[n]);
+        }
+        let mut input_changed = ui.slider("network input size", 32, 1280, &mut self.input.size);
+        input_changed |= ui
+            .input_scalar("input scale", &mut self.input.scale)
+            .build();
+        let tiling = &mut self.input.tiling;
+        input_changed |= ui.checkbox("tiled inference", &mut tiling.enabled);
+        if tiling.enabled {
+            ui.same_line();
+            ui.text(format!("({} model copies)", tiling::threads()));
+            input_changed |= ui.slider("tile size", 128, 2048, &mut tiling.size);
+            input_changed |= ui.slider("tile overlap", 0., 0.5, &mut tiling.overlap);
+        }
+        if input_changed {
+            self.input.size = (self.input.size + 16) / 32 * 32;
+            let _ = self.send(Command::Input(self.input));
+        }
+        ui.slider("confidence", 0.05, 1., &mut self.confidence);
+        ui.slider("NMS overlap", 0., 1., &mut self.nms);
+        self.class_filter.panel(ui, &self.classes, self.confidence);
+        if let Some(ref worker) = self.worker {
+            ui.text(format!("stale frames dropped: {}", worker.dropped));
+        }
+        self.log.panel(ui);
+        self.tracker.panel(ui, &self.classes);
+        self.table(ui);
+    }
+
+    fn table(&self, ui: &imgui::Ui) {
+        if self.objects.is_empty() {
+            return;
+        }
+        let headers = [
+            "object",
+            "score",
+            "DoLP",
+            "AoLP",
+            "diff red",
+            "diff green",
+            "diff blue",
+        ];
+        if let Some(_) = ui.begin_table("detections", headers.len()) {
+            for header in headers {
+                ui.table_setup_column(header);
+            }
+            ui.table_headers_row();
+            for object in &self.objects {
+                ui.table_next_column();
+                ui.text(&object.label);
+                ui.table_next_column();
+                ui.text(format!("{:.2}", object.score));
+                match object.polar {
+                    Some(polar) => {
+                        ui.table_next_column();
+                        ui.text(format!("{:.3} ± {:.3}", polar.dolp_mean, polar.dolp_std));
+                        ui.table_next_column();
+                        ui.text(format!("{:.1}° ± {:.1}°", polar.aolp_mean, polar.aolp_std));
+                    }
+                    None => {
+                        ui.table_next_column();
+                        ui.text("-");
+                        ui.table_next_column();
+                        ui.text("-");
+                    }
+                }
+                for c in (0..3).rev() {
+                    ui.table_next_column();
+                    ui.text(format!("{:.1}", object.difference[c].mean));
+                }
+            }
+        }
+    }
+
+    /// tracks or detections, without the statistics
+    pub fn draw_boxes(&self, mat: &mut Mat) {
+        if self.tracker.enabled {
+            self.tracker.draw(mat, &self.classes, &self.class_filter);
+        } else if let Some(ref detections) = self.detections {
+            draw(
+                mat,
+                &self.filter(detections),
+                &self.classes,
+                &self.class_filter,
+            );
+        }
+    }
+
+    pub fn draw(&self, mat: &mut Mat) {
+        self.draw_boxes(mat);
+        let color: Scalar = [0., 255., 255., 255.].into();
+        let font = imgproc::FONT_HERSHEY_SIMPLEX;
+        for object in &self.objects {
+            let d = object.difference.map(|c| c.mean);
+            let mut lines = vec![format!("diff {:.0} {:.0} {:.0}", d[2], d[1], d[0])];
+            if let Some(polar) = object.polar {
+                let text = format!("DoLP {:.2} AoLP {:.0}", polar.dolp_mean, polar.aolp_mean);
+                lines.insert(0, text);
+            }
+            let bottom = object.rect.y + object.rect.height;
+            for (n, text) in lines.iter().enumerate() {
+                let org = Point::new(object.rect.x, bottom + 14 * (n as i32 + 1));
+                imgproc::put_text_def(mat, text, org, font, 0.4, color).unwrap();
+            }
+        }
+    }
 }
 
-pub fn draw(mat: &mut Mat, detections: &Detections, classes: &mut Option<Classes>) {
-    if classes.is_none() {
-        let raw = fs::read(CLASSES_FILE).unwrap();
-        let string: Vec<String> = String::from_utf8(raw)
-            .unwrap()
-            .split("\n")
-            .map(|x| x.to_string())
-            .collect();
-        *classes = Some(string);
+impl Persist for Detector {
+    fn save(&self, storage: &mut FileStorage) -> Result<()> {
+        profile::save_struct(storage, "model", &self.files)?;
+        storage.write_i32("source", self.source as i32)?;
+        let targets = (0..TARGETS.len()).filter(|n| self.targets[*n]);
+        storage.write_i32("targets", targets.map(|n| 1 << n).sum())?;
+        storage.write_i32("input_size", self.input.size)?;
+        storage.write_f64("input_scale", self.input.scale)?;
+        let tiling = &self.input.tiling;
+        storage.write_i32("tiled", tiling.enabled as i32)?;
+        storage.write_i32("tile_size", tiling.size)?;
+        storage.write_f64("tile_overlap", tiling.overlap as f64)?;
+        storage.write_f64("confidence", self.confidence as f64)?;
+        storage.write_f64("nms", self.nms as f64)?;
+        profile::save_struct(storage, "classes", &self.class_filter)
     }
-    let classes = classes.as_ref().unwrap();
 
+    fn load(&mut self, node: &FileNode) -> Result<()> {
+        profile::load_struct(node, "model", &mut self.files)?;
+        let mut source = self.source as i32;
+        profile::read_i32(node, "source", &mut source)?;
+        self.source = (source.max(0) as usize).min(SOURCES.len() - 1);
+        let mut targets = -1;
+        profile::read_i32(node, "targets", &mut targets)?;
+        if targets >= 0 {
+            self.targets = std::array::from_fn(|n| targets & 1 << n != 0);
+        }
+        profile::read_i32(node, "input_size", &mut self.input.size)?;
+        profile::read_f64(node, "input_scale", &mut self.input.scale)?;
+        let tiling = &mut self.input.tiling;
+        profile::read_bool(node, "tiled", &mut tiling.enabled)?;
+        profile::read_i32(node, "tile_size", &mut tiling.size)?;
+        let mut overlap = tiling.overlap as f64;
+        profile::read_f64(node, "tile_overlap", &mut overlap)?;
+        tiling.overlap = overlap as f32;
+        let mut confidence = self.confidence as f64;
+        profile::read_f64(node, "confidence", &mut confidence)?;
+        self.confidence = confidence as f32;
+        let mut nms = self.nms as f64;
+        profile::read_f64(node, "nms", &mut nms)?;
+        self.nms = nms as f32;
+        profile::load_struct(node, "classes", &mut self.class_filter)
+    }
+}
+
+/// name of the class, or its id past the end of the class names
+pub fn label(classes: &Classes, class_id: ClassID) -> String {
+    match classes.get(class_id as usize) {
+        Some(name) => name.clone(),
+        None => format!("class {}", class_id),
+    }
+}
+
+/// detections above the confidence threshold left after non-maximum suppression
+pub fn filter(detections: &Detections, confidence: f32, nms: f32) -> Vec<tracker::Detection> {
     let (class_ids, scores, rects) = detections;
     let mut indices = Vector::<i32>::new();
-    dnn::nms_boxes_def(&rects, &scores, 0.5, 0.1, &mut indices).unwrap();
+    dnn::nms_boxes_def(rects, scores, confidence, nms, &mut indices).unwrap();
+    indices
+        .iter()
+        .map(|i| tracker::Detection {
+            class: class_ids.get(i as usize).unwrap(),
+            score: scores.get(i as usize).unwrap(),
+            rect: rects.get(i as usize).unwrap(),
+        })
+        .collect()
+}
 
-    for i in indices {
-        let rect: Rect = rects.to_vec()[i as usize];
-        let label = classes[class_ids.to_vec()[i as usize] as usize].as_str();
-        let color: Scalar = [0., 0., 255., 255.].into();
+/// boxes in the colour of their class, labelled with class and score
+pub fn draw(
+    mat: &mut Mat,
+    detections: &[tracker::Detection],
+    classes: &Classes,
+    class_filter: &class_filter::ClassFilter,
+) {
+    for detection in detections {
+        let rect = detection.rect;
+        let color = class_filter.style(detection.class).scalar();
         imgproc::rectangle_def(mat, rect, color).unwrap();
 
-        let text_org = Point::new(rect.x + 10, rect.y + 20);
+        let text_org = Point::new(rect.x, rect.y - 4);
         let font = imgproc::FONT_HERSHEY_SIMPLEX;
-        imgproc::put_text_def(mat, label, text_org, font, 0.5, color).unwrap();
+        let label = format!("{} {:.2}", label(classes, detection.class), detection.score);
+        imgproc::put_text_def(mat, &label, text_org, font, 0.5, color).unwrap();
     }
 }
@@ -0,0 +1,234 @@
+use crate::profile::{self, Persist};
+use crate::*;
+
+/// one bright, strongly polarized blob, as left by specular reflection
+#[derive(Clone, Debug)]
+pub struct Region {
+    pub contour: Vector<Point>,
+    pub rect: Rect,
+    /// pixels
+    pub area: f64,
+    pub centroid: Point2f,
+    pub dolp_mean: f64,
+}
+
+/// classical glare detector: thresholds on DoLP and intensity, cleaned by opening and
+/// closing, its contours reported as regions. Needs no model files.
+pub struct Glare {
+    pub enabled: bool,
+    pub min_dolp: f32,
+    /// 8-bit gray level of the mean of both feeds
+    pub min_intensity: f32,
+    /// side of the elliptical morphology kernel, 1 to skip the cleanup
+    pub kernel: i32,
+    /// regions below this many pixels are dropped
+    pub min_area: f32,
+    /// in the order of `detection::TARGETS`
+    pub targets: [bool; 5],
+    pub regions: Vec<Region>,
+}
+
+impl Default for Glare {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_dolp: 0.3,
+            min_intensity: 200.,
+            kernel: 5,
+            min_area: 50.,
+            targets: [true, false, false, true, false],
+            regions: Vec::new(),
+        }
+    }
+}
+
+/// 255 where `mat` is at least `threshold`, as CV_8U
+fn binary(mat: &Mat, threshold: f64, max: f64) -> Mat {
+    let (mut thresholded, mut mask) = (Mat::default(), Mat::default());
+    imgproc::threshold(
+        mat,
+        &mut thresholded,
+        threshold,
+        max,
+        imgproc::THRESH_BINARY,
+    )
+    .unwrap();
+    thresholded
+        .convert_to(&mut mask, CV_8U, 255. / max, 0.)
+        .unwrap();
+    mask
+}
+
+impl Glare {
+    /// finds the regions of the registered feeds
+    pub fn update(&mut self, feeds: [&Mat; 2], polarimetry: &polarimetry::Polarimetry) {
+        self.regions.clear();
+        let dolp = &polarimetry.dolp;
+        if !self.enabled || feeds[0].empty() || dolp.empty() {
+            return;
+        }
+        let size = dolp.size().unwrap();
+        if feeds.iter().any(|f| f.size().unwrap() != size) {
+            return;
+        }
+
+        let [left, right] = feeds.map(|f| {
+            let mut gray = Mat::default();
+            imgproc::cvt_color_def(f, &mut gray, imgproc::COLOR_BGR2GRAY).unwrap();
+            gray
+        });
+        let mut intensity = Mat::default();
+        add_weighted_def(&left, 0.5, &right, 0.5, 0., &mut intensity).unwrap();
+        // THRESH_BINARY keeps values strictly above the threshold
+        let bright = binary(&intensity, self.min_intensity as f64 - 1., 255.);
+        let polarized = binary(dolp, self.min_dolp as f64 - 1e-6, 1.);
+        let mut mask = Mat::default();
+        bitwise_and_def(&bright, &polarized, &mut mask).unwrap();
+
+        if self.kernel > 1 {
+            let ksize = Size::new(self.kernel, self.kernel);
+            let kernel =
+                imgproc::get_structuring_element_def(imgproc::MORPH_ELLIPSE, ksize).unwrap();
+            for op in [imgproc::MORPH_OPEN, imgproc::MORPH_CLOSE] {
+                imgproc::morphology_ex_def(&mask.clone(), &mut mask, op, &kernel).unwrap();
+            }
+        }
+
+        let mut contours = Vector::<Vector<Point>>::new();
+        imgproc::find_contours_def(
+            &mask,
+            &mut contours,
+            imgproc::RETR_EXTERNAL,
+            imgproc::CHAIN_APPROX_SIMPLE,
+        )
+        .unwrap();
+        for contour in contours {
+            let area = imgproc::contour_area_def(&contour).unwrap();
+            if area < self.min_area as f64 {
+                continue;
+            }
+            let rect = imgproc::bounding_rect(&contour).unwrap();
+            let moments = imgproc::moments_def(&contour).unwrap();
+            let centroid = if moments.m00 > 0. {
+                Point2f::new(
+                    (moments.m10 / moments.m00) as f32,
+                    (moments.m01 / moments.m00) as f32,
+                )
+            } else {
+                Point2f::new(
+                    rect.x as f32 + rect.width as f32 / 2.,
+                    rect.y as f32 + rect.height as f32 / 2.,
+                )
+            };
+            let mut inside = Mat::zeros_size(rect.size(), CV_8UC1)
+                .unwrap()
+                .to_mat()
+                .unwrap();
+            imgproc::draw_contours(
+                &mut inside,
+                &Vector::<Vector<Point>>::from_iter([contour.clone()]),
+                0,
+                Scalar::all(255.),
+                imgproc::FILLED,
+                imgproc::LINE_8,
+                &no_array(),
+                0,
+                Point::new(-rect.x, -rect.y),
+            )
+            .unwrap();
+            let dolp_mean = mean(&dolp.roi(rect).unwrap(), &inside).unwrap()[0];
+            self.regions.push(Region {
+                contour,
+                rect,
+                area,
+                centroid,
+                dolp_mean,
+            });
+        }
+        self.regions.sort_by(|a, b| b.area.total_cmp(&a.area));
+    }
+
+    pub fn draw(&self, mat: &mut Mat) {
+        let color: Scalar = [255., 0., 255., 255.].into();
+        let contours: Vector<Vector<Point>> =
+            self.regions.iter().map(|r| r.contour.clone()).collect();
+        imgproc::draw_contours_def(mat, &contours, -1, color).unwrap();
+        let font = imgproc::FONT_HERSHEY_SIMPLEX;
+        for (n, region) in self.regions.iter().enumerate() {
+            let text = format!("glare {} DoLP {:.2}", n + 1, region.dolp_mean);
+            let org = Point::new(region.rect.x, region.rect.y - 4);
+            imgproc::put_text_def(mat, &text, org, font, 0.4, color).unwrap();
+        }
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui) {
+        ui.checkbox("glare detection", &mut self.enabled);
+        if !self.enabled {
+            return;
+        }
+        ui.slider("glare min DoLP", 0., 1., &mut self.min_dolp);
+        ui.slider("glare min intensity", 0., 255., &mut self.min_intensity);
+        ui.slider("glare kernel", 1, 31, &mut self.kernel);
+        ui.slider("glare min area", 0., 5000., &mut self.min_area);
+        ui.text("draw on:");
+        for (n, name) in TARGETS.iter().enumerate() {
+            ui.same_line();
+            ui.checkbox(format!("{}##glare target", name), &mut self.targets[n]);
+        }
+        if self.regions.is_empty() {
+            ui.text("no glare regions");
+            return;
+        }
+        if let Some(_) = ui.begin_table("glare regions", 4) {
+            for header in ["region", "area", "centroid", "DoLP"] {
+                ui.table_setup_column(header);
+            }
+            ui.table_headers_row();
+            for (n, region) in self.regions.iter().enumerate() {
+                ui.table_next_column();
+                ui.text((n + 1).to_string());
+                ui.table_next_column();
+                ui.text(format!("{:.0}", region.area));
+                ui.table_next_column();
+                ui.text(format!(
+                    "{:.1}, {:.1}",
+                    region.centroid.x, region.centroid.y
+                ));
+                ui.table_next_column();
+                ui.text(format!("{:.3}", region.dolp_mean));
+            }
+        }
+    }
+}
+
+impl Persist for Glare {
+    fn save(&self, storage: &mut FileStorage) -> Result<()> {
+        storage.write_i32("enabled", self.enabled as i32)?;
+        storage.write_f64("min_dolp", self.min_dolp as f64)?;
+        storage.write_f64("min_intensity", self.min_intensity as f64)?;
+        storage.write_i32("kernel", self.kernel)?;
+        storage.write_f64("min_area", self.min_area as f64)?;
+        let targets = (0..TARGETS.len()).filter(|n| self.targets[*n]);
+        storage.write_i32("targets", targets.map(|n| 1 << n).sum())
+    }
+
+    fn load(&mut self, node: &FileNode) -> Result<()> {
+        profile::read_bool(node, "enabled", &mut self.enabled)?;
+        for (key, value) in [
+            ("min_dolp", &mut self.min_dolp),
+            ("min_intensity", &mut self.min_intensity),
+            ("min_area", &mut self.min_area),
+        ] {
+            let mut float = *value as f64;
+            profile::read_f64(node, key, &mut float)?;
+            *value = float as f32;
+        }
+        profile::read_i32(node, "kernel", &mut self.kernel)?;
+        let mut targets = -1;
+        profile::read_i32(node, "targets", &mut targets)?;
+        if targets >= 0 {
+            self.targets = std::array::from_fn(|n| targets & 1 << n != 0);
+        }
+        Ok(())
+    }
+}
@@ -60,6 +60,23 @@ impl Image {
         imgui::Image::new(self.texture_id.unwrap(), size.to_array())
     }
 
+    /// mouse state over the image, call right after building the image returned by `make`
+    pub fn canvas(&self, ui: &imgui::Ui, window: usize, size: Size) -> Canvas {
+        let origin = ui.item_rect_min();
+        ui.set_cursor_screen_pos(origin);
+        ui.invisible_button("##canvas", size.to_array());
+        let mut canvas = Canvas {
+            window,
+            origin,
+            size,
+            source: self.mat.size().unwrap(),
+            hovered: ui.is_item_hovered(),
+            mouse: Point2f::default(),
+        };
+        canvas.mouse = canvas.to_source(ui.io().mouse_pos);
+        canvas
+    }
+
     fn init(&mut self, renderer: &mut igr::AutoRenderer) {
         self.texture = Some(unsafe { renderer.gl_context().create_texture() }.unwrap());
         self.texture_id = Some(
@@ -70,3 +87,38 @@ impl Image {
         );
     }
 }
+
+/// a built image under the mouse, the invisible button over it keeps drags from moving the window
+pub struct Canvas {
+    /// index of the feed window
+    pub window: usize,
+    pub origin: [f32; 2],
+    pub size: Size,
+    pub source: Size,
+    pub hovered: bool,
+    /// mouse position in source pixel coordinates
+    pub mouse: Point2f,
+}
+
+impl Canvas {
+    /// maps a screen position back through the resize to source pixel coordinates
+    pub fn to_source(&self, pos: [f32; 2]) -> Point2f {
+        Point2f::new(
+            (pos[0] - self.origin[0]) * self.source.width as f32 / self.size.width as f32,
+            (pos[1] - self.origin[1]) * self.source.height as f32 / self.size.height as f32,
+        )
+    }
+
+    /// maps source pixel coordinates to a screen position
+    pub fn to_screen(&self, point: Point2f) -> [f32; 2] {
+        [
+            self.origin[0] + point.x * self.size.width as f32 / self.source.width as f32,
+            self.origin[1] + point.y * self.size.height as f32 / self.source.height as f32,
+        ]
+    }
+
+    /// mouse position rounded to a source pixel
+    pub fn pixel(&self) -> Point {
+        Point::new(self.mouse.x.round() as i32, self.mouse.y.round() as i32)
+    }
+}
@@ -0,0 +1,112 @@
+use crate::*;
+
+/// values of every stage of the pipeline at one source pixel
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Pixel {
+    pub point: Point,
+    /// BGR of both cameras as read, before the shift, alignment and null subtraction
+    pub cameras: [Option<Vec3b>; 2],
+    pub null: [Option<Vec3b>; 2],
+    pub difference: Option<Vec3b>,
+    pub dolp: Option<f32>,
+    pub aolp: Option<f32>,
+}
+
+/// pixel under the mouse in any feed window, with a crosshair at the same spot in the others
+#[derive(Default)]
+pub struct Inspector {
+    /// feed window index and source pixel hovered in the last frame
+    pub hover: Option<(usize, Point)>,
+    pub pixel: Option<Pixel>,
+}
+
+fn at<T: DataType + Copy>(mat: &Mat, point: Point) -> Option<T> {
+    let inside = point.x >= 0 && point.y >= 0 && point.x < mat.cols() && point.y < mat.rows();
+    if mat.empty() || !inside || mat.typ() != T::opencv_type() {
+        return None;
+    }
+    mat.at_2d::<T>(point.y, point.x).ok().copied()
+}
+
+impl Inspector {
+    /// samples the camera frames, call before the right camera is shifted
+    pub fn read_cameras(&mut self, left: &Mat, right: &Mat) {
+        self.pixel = self.hover.map(|(_, point)| Pixel {
+            point,
+            cameras: [at(left, point), at(right, point)],
+            ..Default::default()
+        });
+    }
+
+    /// samples the null frames, the difference and the polarimetry, call before overlays are drawn
+    pub fn read(
+        &mut self,
+        null: [&Mat; 2],
+        difference: &Mat,
+        polarimetry: &polarimetry::Polarimetry,
+    ) {
+        if let Some(ref mut pixel) = self.pixel {
+            pixel.null = null.map(|mat| at(mat, pixel.point));
+            pixel.difference = at(difference, pixel.point);
+            pixel.dolp = at(&polarimetry.dolp, pixel.point);
+            pixel.aolp = at(&polarimetry.aolp, pixel.point);
+        }
+    }
+
+    /// tooltip while hovered and crosshair while another window is hovered, call after `Image::canvas`
+    pub fn interact(
+        &mut self,
+        ui: &imgui::Ui,
+        canvas: &image::Canvas,
+        last: Option<(usize, Point)>,
+    ) {
+        if canvas.hovered {
+            let mouse = canvas.mouse;
+            let point = Point::new(mouse.x.floor() as i32, mouse.y.floor() as i32);
+            self.hover = Some((canvas.window, point));
+            if let Some(ref pixel) = self.pixel {
+                ui.tooltip(|| pixel.tooltip(ui));
+            }
+        }
+        let Some((window, point)) = last else {
+            return;
+        };
+        if window == canvas.window {
+            return;
+        }
+        let center = canvas.to_screen(Point2f::new(point.x as f32 + 0.5, point.y as f32 + 0.5));
+        let min = canvas.origin;
+        let max = [
+            min[0] + canvas.size.width as f32,
+            min[1] + canvas.size.height as f32,
+        ];
+        let draw_list = ui.get_window_draw_list();
+        let color = [1., 1., 0., 0.8];
+        draw_list
+            .add_line([min[0], center[1]], [max[0], center[1]], color)
+            .build();
+        draw_list
+            .add_line([center[0], min[1]], [center[0], max[1]], color)
+            .build();
+    }
+}
+
+impl Pixel {
+    fn tooltip(&self, ui: &imgui::Ui) {
+        let bgr = |v: Option<Vec3b>| match v {
+            Some(v) => format!("R {:3} G {:3} B {:3}", v[2], v[1], v[0]),
+            None => "-".into(),
+        };
+        ui.text(format!("pixel ({}, {})", self.point.x, self.point.y));
+        ui.separator();
+        ui.text(format!("left        {}", bgr(self.cameras[0])));
+        ui.text(format!("right       {}", bgr(self.cameras[1])));
+        ui.text(format!("left null   {}", bgr(self.null[0])));
+        ui.text(format!("right null  {}", bgr(self.null[1])));
+        ui.text(format!("difference  {}", bgr(self.difference)));
+        match (self.dolp, self.aolp) {
+            (Some(dolp), Some(aolp)) => ui.text(format!("DoLP {:.3}  AoLP {:.1}°", dolp, aolp)),
+            _ => ui.text("DoLP/AoLP -"),
+        }
+    }
+}
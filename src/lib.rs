@@ -1,9 +1,31 @@
+pub mod anchor;
+pub mod annotations;
 pub mod app;
+pub mod batch;
 pub mod calibrate;
+pub mod class_filter;
+pub mod decoder;
 pub mod detection;
+pub mod glare;
 pub mod image;
+pub mod inspector;
+pub mod line_profile;
+pub mod logger;
+pub mod motion;
+pub mod orientation;
+pub mod plot;
+pub mod polarimetry;
+pub mod profile;
+pub mod recorder;
+pub mod registration;
+pub mod roi;
+pub mod sink;
+pub mod stats;
+pub mod tiling;
+pub mod tracker;
 pub mod utils;
 pub mod window;
+pub mod worker;
 
 pub use imgui as im;
 pub use std::{env, os, str};
@@ -0,0 +1,207 @@
+use crate::*;
+use imgui::MouseButton;
+use std::io::Write;
+
+const FEEDS: [&str; 3] = ["left", "right", "subtracted"];
+
+/// values sampled at one point along the line
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Sample {
+    /// pixels from the start of the line
+    pub distance: f64,
+    /// BGR
+    pub bgr: [f64; 3],
+    pub dolp: Option<f64>,
+}
+
+/// intensity and DoLP along a line drawn over any feed window
+#[derive(Default)]
+pub struct LineProfile {
+    /// start and end in source pixel coordinates
+    pub line: Option<[Point2f; 2]>,
+    /// index into `FEEDS` the channel intensities are sampled from
+    pub feed: usize,
+    pub samples: Vec<Sample>,
+    pub markers: bool,
+    /// feed window index of the current drag
+    drag: Option<usize>,
+    status: String,
+}
+
+/// bilinear interpolation of `at(x, y)` over a mat of `size`, clamped to the border
+fn bilinear(size: Size, p: Point2f, at: impl Fn(i32, i32) -> f64) -> f64 {
+    let (w, h) = (size.width, size.height);
+    let x = (p.x as f64).clamp(0., (w - 1) as f64);
+    let y = (p.y as f64).clamp(0., (h - 1) as f64);
+    let (x0, y0) = (x.floor() as i32, y.floor() as i32);
+    let (x1, y1) = ((x0 + 1).min(w - 1), (y0 + 1).min(h - 1));
+    let (fx, fy) = (x - x0 as f64, y - y0 as f64);
+    let top = at(x0, y0) * (1. - fx) + at(x1, y0) * fx;
+    let bottom = at(x0, y1) * (1. - fx) + at(x1, y1) * fx;
+    top * (1. - fy) + bottom * fy
+}
+
+impl LineProfile {
+    /// handles drawing the line with a left drag over a feed image
+    pub fn interact(&mut self, ui: &imgui::Ui, canvas: &image::Canvas) {
+        if canvas.hovered && ui.is_mouse_clicked(MouseButton::Left) {
+            self.drag = Some(canvas.window);
+            self.line = Some([canvas.mouse, canvas.mouse]);
+        }
+        if self.drag != Some(canvas.window) {
+            return;
+        }
+        if let Some(ref mut line) = self.line {
+            line[1] = canvas.mouse;
+        }
+        if ui.is_mouse_released(MouseButton::Left) {
+            self.drag = None;
+        }
+    }
+
+    /// samples the selected feed and DoLP once per pixel of length along the line
+    pub fn update(&mut self, feeds: &[&Mat; 3], polarimetry: &polarimetry::Polarimetry) {
+        self.samples.clear();
+        let (Some([start, end]), feed) = (self.line, feeds[self.feed]) else {
+            return;
+        };
+        if feed.empty() || feed.typ() != CV_8UC3 {
+            return;
+        }
+        let size = feed.size().unwrap();
+        let dolp = &polarimetry.dolp;
+        let has_polar = !dolp.empty() && dolp.size().unwrap() == size;
+
+        let delta = end - start;
+        let length = delta.x.hypot(delta.y) as f64;
+        let steps = length.ceil().max(1.) as usize;
+        self.samples = (0..=steps)
+            .map(|i| {
+                let t = i as f64 / steps as f64;
+                let p = start + delta * t as f32;
+                Sample {
+                    distance: t * length,
+                    bgr: [0, 1, 2].map(|c| {
+                        bilinear(size, p, |x, y| feed.at_2d::<Vec3b>(y, x).unwrap()[c] as f64)
+                    }),
+                    dolp: has_polar.then(|| {
+                        bilinear(size, p, |x, y| *dolp.at_2d::<f32>(y, x).unwrap() as f64)
+                    }),
+                }
+            })
+            .collect();
+    }
+
+    pub fn draw(&self, mat: &mut Mat) {
+        let Some([start, end]) = self.line else {
+            return;
+        };
+        let to_point = |p: Point2f| Point::new(p.x.round() as i32, p.y.round() as i32);
+        let yellow: Scalar = [0., 255., 255., 255.].into();
+        imgproc::line_def(mat, to_point(start), to_point(end), yellow).unwrap();
+        imgproc::circle_def(mat, to_point(start), 3, yellow).unwrap();
+    }
+
+    pub fn window(&mut self, ui: &imgui::Ui) {
+        if self.line.is_none() {
+            return;
+        }
+        ui.window("line profile")
+            .size([500., 420.], im::Condition::FirstUseEver)
+            .build(|| self.contents(ui));
+    }
+
+    fn contents(&mut self, ui: &imgui::Ui) {
+        ui.set_next_item_width(150.);
+        ui.combo_simple_string("feed", &mut self.feed, &FEEDS);
+        ui.same_line();
+        ui.checkbox("markers", &mut self.markers);
+        ui.same_line();
+        if ui.button("export CSV") {
+            self.status = match self.export() {
+                Ok(path) => format!("saved {}", path),
+                Err(err) => format!("export error: {}", err),
+            };
+        }
+        ui.same_line();
+        if ui.button("clear") {
+            self.line = None;
+            self.samples.clear();
+        }
+        if let Some([start, end]) = self.line {
+            ui.text(format!(
+                "({:.1}, {:.1}) to ({:.1}, {:.1}), {} samples",
+                start.x,
+                start.y,
+                end.x,
+                end.y,
+                self.samples.len()
+            ));
+        }
+        if !self.status.is_empty() {
+            ui.text(&self.status);
+        }
+
+        let length = self.samples.last().map_or(1., |s| s.distance.max(1.));
+        let x = [0., length];
+        let width = ui.content_region_avail()[0];
+        let colors = [[0.4, 0.6, 1., 1.], [0.4, 1., 0.4, 1.], [1., 0.4, 0.4, 1.]];
+        let lines: Vec<plot::Line> = (0..3)
+            .rev()
+            .map(|c| plot::Line {
+                label: ["blue", "green", "red"][c].into(),
+                color: colors[c],
+                points: self
+                    .samples
+                    .iter()
+                    .map(|s| [s.distance, s.bgr[c]])
+                    .collect(),
+            })
+            .collect();
+        ui.text("intensity");
+        plot::draw(ui, [width, 150.], &lines, x, Some([0., 255.]), self.markers);
+
+        let dolp = plot::Line {
+            label: "DoLP".into(),
+            color: [1., 1., 0.4, 1.],
+            points: self
+                .samples
+                .iter()
+                .filter_map(|s| s.dolp.map(|d| [s.distance, d]))
+                .collect(),
+        };
+        ui.text("DoLP");
+        plot::draw(ui, [width, 150.], &[dolp], x, Some([0., 1.]), self.markers);
+    }
+
+    fn export(&self) -> std::io::Result<String> {
+        let path = utils::get_save_filepath("line.csv");
+        let mut writer = std::io::BufWriter::new(fs::File::create(&path)?);
+        writeln!(writer, "distance,x,y,feed,red,green,blue,dolp")?;
+        let [start, end] = self.line.unwrap_or_default();
+        let length = self.samples.last().map_or(0., |s| s.distance);
+        for sample in &self.samples {
+            let t = if length > 0. {
+                sample.distance / length
+            } else {
+                0.
+            };
+            let p = start + (end - start) * t as f32;
+            let dolp = sample.dolp.map(|d| d.to_string()).unwrap_or_default();
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{}",
+                sample.distance,
+                p.x,
+                p.y,
+                FEEDS[self.feed],
+                sample.bgr[2],
+                sample.bgr[1],
+                sample.bgr[0],
+                dolp
+            )?;
+        }
+        writer.flush()?;
+        Ok(path)
+    }
+}
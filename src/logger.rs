@@ -0,0 +1,204 @@
+use crate::*;
+use std::io::{BufWriter, Write};
+
+pub const FORMATS: [&str; 2] = ["CSV", "JSON Lines"];
+const FEEDS: [&str; 3] = ["left", "right", "subtracted"];
+const CHANNELS: [&str; 3] = ["red", "green", "blue"];
+
+/// streams the roi statistics of every logged frame to the output folder
+pub struct Logger {
+    pub format: usize,
+    /// log every n-th frame
+    pub decimation: i32,
+    pub path: Option<String>,
+    pub rows: u64,
+    writer: Option<BufWriter<fs::File>>,
+    error: Option<String>,
+}
+
+impl Default for Logger {
+    fn default() -> Self {
+        Self {
+            format: 0,
+            decimation: 1,
+            path: None,
+            rows: 0,
+            writer: None,
+            error: None,
+        }
+    }
+}
+
+fn column_name(name: &str) -> String {
+    name.to_lowercase().replace(' ', "_")
+}
+
+impl Logger {
+    pub fn start(&mut self) -> std::io::Result<()> {
+        let path = utils::get_save_filepath(["rois.csv", "rois.jsonl"][self.format]);
+        let mut writer = BufWriter::new(fs::File::create(&path)?);
+        if self.format == 0 {
+            let columns = stats::COLUMNS.iter().chain(&stats::POLAR_COLUMNS);
+            let columns: Vec<String> = ["timestamp", "frame", "profile", "roi", "feed", "channel"]
+                .iter()
+                .chain(columns)
+                .map(|c| column_name(c))
+                .collect();
+            writeln!(writer, "{}", columns.join(","))?;
+        }
+        self.writer = Some(writer);
+        self.path = Some(path);
+        self.rows = 0;
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut writer) = self.writer.take() {
+            let _ = writer.flush();
+        }
+    }
+
+    pub fn log(&mut self, frame: u64, profile: &str, rois: &roi::Rois) {
+        let Some(ref mut writer) = self.writer else {
+            return;
+        };
+        if frame % self.decimation.max(1) as u64 != 0 {
+            return;
+        }
+        let timestamp = utils::timestamp();
+        let result = match self.format {
+            0 => write_csv(writer, timestamp, frame, profile, rois),
+            _ => write_json(writer, timestamp, frame, profile, rois),
+        };
+        match result {
+            Ok(()) => self.rows += 1,
+            Err(err) => {
+                self.error = Some(err.to_string());
+                self.stop();
+            }
+        }
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui) {
+        ui.text("measurement log:");
+        ui.same_line();
+        match self.writer {
+            Some(_) => {
+                if ui.button("stop##log") {
+                    self.stop();
+                }
+                ui.same_line();
+                let path = self.path.as_deref().unwrap_or_default();
+                ui.text(format!("{} frames to {}", self.rows, path));
+            }
+            None => {
+                if ui.button("start##log") {
+                    self.error = self.start().err().map(|err| err.to_string());
+                }
+                ui.same_line();
+                ui.set_next_item_width(120.);
+                ui.combo_simple_string("##log format", &mut self.format, &FORMATS);
+                ui.slider("log every n frames", 1, 100, &mut self.decimation);
+            }
+        }
+        if let Some(ref error) = self.error {
+            ui.text(format!("log error: {}", error));
+        }
+    }
+}
+
+impl Drop for Logger {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+fn write_csv(
+    writer: &mut impl Write,
+    timestamp: f64,
+    frame: u64,
+    profile: &str,
+    rois: &roi::Rois,
+) -> std::io::Result<()> {
+    for (roi, readings) in rois.list.iter().zip(&rois.readings) {
+        let polar: Vec<String> = match readings.polar {
+            Some(polar) => polar.values().iter().map(|v| v.to_string()).collect(),
+            None => vec![String::new(); stats::POLAR_COLUMNS.len()],
+        };
+        for (n, bgr) in readings.feeds.iter().enumerate() {
+            for i in 0..3 {
+                let values = bgr[2 - i].values().map(|v| v.to_string());
+                let fields: Vec<String> = [
+                    format!("{:.6}", timestamp),
+                    frame.to_string(),
+                    utils::csv_field(profile),
+                    utils::csv_field(&roi.name),
+                    FEEDS[n].into(),
+                    CHANNELS[i].into(),
+                ]
+                .into_iter()
+                .chain(values)
+                .chain(polar.iter().cloned())
+                .collect();
+                writeln!(writer, "{}", fields.join(","))?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_json(
+    writer: &mut impl Write,
+    timestamp: f64,
+    frame: u64,
+    profile: &str,
+    rois: &roi::Rois,
+) -> std::io::Result<()> {
+    let object = |names: &[&str], values: &[f64]| {
+        let fields: Vec<String> = names
+            .iter()
+            .zip(values)
+            .map(|(n, v)| format!("\"{}\":{}", column_name(n), utils::json_number(*v)))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    };
+    let rois: Vec<String> = rois
+        .list
+        .iter()
+        .zip(&rois.readings)
+        .map(|(roi, readings)| {
+            let feeds: Vec<String> = readings
+                .feeds
+                .iter()
+                .enumerate()
+                .map(|(n, bgr)| {
+                    let channels: Vec<String> = (0..3)
+                        .map(|i| {
+                            let values = object(&stats::COLUMNS, &bgr[2 - i].values());
+                            format!("\"{}\":{}", CHANNELS[i], values)
+                        })
+                        .collect();
+                    format!("\"{}\":{{{}}}", FEEDS[n], channels.join(","))
+                })
+                .collect();
+            let polar = match readings.polar {
+                Some(polar) => object(&stats::POLAR_COLUMNS, &polar.values()),
+                None => "null".into(),
+            };
+            format!(
+                "{{\"name\":{},\"feeds\":{{{}}},\"polar\":{}}}",
+                utils::json_string(&roi.name),
+                feeds.join(","),
+                polar
+            )
+        })
+        .collect();
+    writeln!(
+        writer,
+        "{{\"timestamp\":{:.6},\"frame\":{},\"profile\":{},\"rois\":[{}]}}",
+        timestamp,
+        frame,
+        utils::json_string(profile),
+        rois.join(",")
+    )
+}
@@ -6,81 +6,230 @@ struct Args {
     detection: bool,
     #[arg(short, long, default_value_t = false)]
     camera: bool,
+    /// profile to load at startup, from the profiles folder
+    #[arg(short, long)]
+    profile: Option<String>,
+    /// detection model weights, overrides the profile
+    #[arg(long)]
+    weights: Option<String>,
+    /// detection network config, overrides the profile
+    #[arg(long)]
+    model_config: Option<String>,
+    /// detection class names, one per line, overrides the profile
+    #[arg(long)]
+    classes: Option<String>,
+    /// detection model family: 0 Darknet YOLOv3, 1 ONNX YOLOv5, 2 ONNX YOLOv8, 3 ONNX YOLOv10
+    #[arg(long, value_parser = clap::value_parser!(u8).range(0..4))]
+    model_family: Option<u8>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum Command {
+    /// runs detection without a window over a recorded video or an image folder
+    Detect {
+        /// video file or folder of images
+        #[arg(short, long)]
+        input: String,
+        /// folder for the annotated frames and the detections.jsonl log
+        #[arg(short, long)]
+        output: String,
+        /// also writes a COCO annotation file next to every frame
+        #[arg(long, default_value_t = false)]
+        coco: bool,
+    },
 }
 
 #[derive(Default)]
 struct State {
+    /// name in the profile field, edited freely before a save or load
+    profile: String,
+    profile_status: String,
+    /// profile the current settings were last loaded from or saved to, empty for none
+    active_profile: String,
+    frame: u64,
     base_px: i32,
     win_size: i32,
-    win_shift: [i32; 2],
     cam_shift: [i32; 2],
-    writer: Option<videoio::VideoWriter>,
+    /// offset of the left roi from the center one, when the default rois are seeded
+    seed_shift: [i32; 2],
+    orientation: [orientation::Orientation; 2],
+    /// orientation the null frames were saved with
+    null_orientation: [orientation::Orientation; 2],
+    rois: roi::Rois,
+    line_profile: line_profile::LineProfile,
+    inspector: inspector::Inspector,
+    plots: plot::Plots,
+    logger: logger::Logger,
+    detector: detection::Detector,
+    glare: glare::Glare,
+    motion: motion::Motion,
+    alignment: registration::Alignment,
+    polarimetry: polarimetry::Polarimetry,
+    recorder: recorder::Recorder,
 }
 
 type Cameras = [videoio::VideoCapture; 2];
-type Feeds = [image::Image; 5];
+type Feeds = [image::Image; 7];
 
 fn main() {
     let args = Args::parse();
     let mut s = State {
+        profile: "default".into(),
+        profile_status: String::new(),
+        active_profile: String::new(),
+        frame: 0,
         base_px: 80,
         win_size: 35,
-        win_shift: [-93, 0],
         cam_shift: [-138, -50],
-        writer: None,
+        seed_shift: [-93, 0],
+        orientation: default_orientation(args.camera),
+        null_orientation: default_orientation(args.camera),
+        rois: Default::default(),
+        line_profile: Default::default(),
+        inspector: Default::default(),
+        plots: Default::default(),
+        logger: Default::default(),
+        detector: Default::default(),
+        glare: Default::default(),
+        motion: Default::default(),
+        alignment: Default::default(),
+        polarimetry: Default::default(),
+        recorder: Default::default(),
     };
+    if let Some(ref name) = args.profile {
+        if let Err(err) = profile::load(name, &mut s) {
+            eprintln!("could not load profile {}: {}", name, err.message);
+            std::process::exit(1);
+        }
+        s.profile = name.clone();
+        s.active_profile = name.clone();
+    }
+
+    let files = &mut s.detector.files;
+    if let Some(family) = args.model_family {
+        files.family = family as usize;
+    }
+    for (arg, file) in [
+        (&args.weights, &mut files.weights),
+        (&args.model_config, &mut files.config),
+        (&args.classes, &mut files.classes),
+    ] {
+        if let Some(arg) = arg {
+            *file = arg.clone();
+        }
+    }
+
+    if let Some(Command::Detect {
+        ref input,
+        ref output,
+        coco,
+    }) = args.command
+    {
+        match batch::run(&mut s.detector, input, output, coco) {
+            Ok(frames) => println!("{} frames written to {}", frames, output),
+            Err(err) => {
+                eprintln!("batch detection failed: {}", err);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
 
     let mut cameras = get_cameras(!args.camera);
     let mut feeds: Feeds = Default::default();
 
-    let mut channels = Channels::new();
-    let mut classes: Option<Classes> = None;
     if args.detection {
-        detection::initialize_thread(channels.channel_there);
+        if let Err(err) = s.detector.start() {
+            eprintln!("could not start detection: {}", err);
+            std::process::exit(1);
+        }
     }
 
     window::create(|ui, renderer| {
-        if !read_cameras(&mut cameras, &mut feeds, !args.camera) {
+        if !read_cameras(&mut cameras, &mut feeds, !args.camera, &s.orientation) {
             return;
         };
+        s.frame += 1;
 
         let img_size = Size::new(s.base_px * 4, s.base_px * 3);
-        let [f0, f1, f2, f00, f10] = &mut feeds;
+        let [f0, f1, f2, f00, f10, dolp, aolp] = &mut feeds;
 
-        if !args.camera {
-            flip(&f0.mat.clone(), &mut f0.mat, -1).unwrap();
-            flip(&f1.mat.clone(), &mut f1.mat, 0).unwrap();
-        }
+        s.recorder.raw([&f0.mat, &f1.mat]);
+        s.inspector.read_cameras(&f0.mat, &f1.mat);
         shift_cameras(&s, &mut f1.mat);
+        s.alignment.update(&f0.mat, &f1.mat);
+        s.rois.track(&f0.mat);
 
         {
-            // DoLP = S1 / S0 = (I90 - I0) / (I90 + I0)
-            //let mut sub = Mat::default();
-            if !&f00.mat.empty() && !f10.mat.empty() {
+            if s.null_orientation != s.orientation {
+                f00.mat.set(Mat::default()).unwrap();
+                f10.mat.set(Mat::default()).unwrap();
+                s.null_orientation = s.orientation;
+            }
+            let fits = |a: &Mat, b: &Mat| a.size().unwrap() == b.size().unwrap();
+            let nulls = !f00.mat.empty() && !f10.mat.empty();
+            let nulls_fit = fits(&f0.mat, &f00.mat) && fits(&f1.mat, &f10.mat);
+            if nulls && nulls_fit {
                 absdiff(&f0.mat.clone(), &f00.mat, &mut f0.mat).unwrap();
                 absdiff(&f1.mat.clone(), &f10.mat, &mut f1.mat).unwrap();
             }
-            absdiff(&f0.mat, &f1.mat, &mut f2.mat).unwrap();
-            //divide2(&f2.mat.clone(), &f1.mat, &mut f2.mat, 255., CV_8UC3).unwrap();
+            // cameras of different sizes or orientations have nothing to subtract
+            if fits(&f0.mat, &f1.mat) {
+                absdiff(&f0.mat, &f1.mat, &mut f2.mat).unwrap();
+            } else {
+                f2.mat.set(Mat::default()).unwrap();
+            }
+            let feeds = [&mut dolp.mat, &mut aolp.mat];
+            if nulls && !nulls_fit {
+                let status = "the null frames differ in size from the cameras, reset them";
+                s.polarimetry.clear(feeds, status);
+            } else {
+                s.polarimetry.update(&f0.mat, &f1.mat, s.win_size, feeds);
+            }
         }
+        s.inspector
+            .read([&f00.mat, &f10.mat], &f2.mat, &s.polarimetry);
+        s.rois
+            .seed(f0.mat.size().unwrap(), s.win_size, s.seed_shift);
+        s.rois.measure(&[&f0.mat, &f1.mat, &f2.mat], &s.polarimetry);
+        s.line_profile
+            .update(&[&f0.mat, &f1.mat, &f2.mat], &s.polarimetry);
+        s.glare.update([&f0.mat, &f1.mat], &s.polarimetry);
+        s.motion
+            .update([&f0.mat, &f1.mat, &f2.mat, &dolp.mat, &aolp.mat][s.motion.source]);
+        s.recorder
+            .clean([&f0.mat, &f1.mat, &f2.mat, &dolp.mat, &aolp.mat]);
+        s.plots.record(&s.rois);
+        s.logger.log(s.frame, &s.active_profile, &s.rois);
 
-        if args.detection {
-            get_detections(
-                &channels.channel_here,
-                &mut channels.body,
-                &mut channels.first_sent,
-                &mut feeds,
-                &mut classes,
-            );
+        if s.detector.running() {
+            get_detections(&mut feeds, &mut s.detector, &s.polarimetry, s.frame);
         }
 
-        let mut mini: [Rect; 2] = Default::default();
-        draw_rois(&s, &mut feeds, &mut mini);
-        all_feed_windows(ui, renderer, &mut feeds, img_size);
+        draw_overlays(&s, &mut feeds);
+        if s.motion.enabled && s.motion.trigger {
+            s.recorder.follow(s.motion.recording());
+        }
+        let [f0, f1, f2, _, _, dolp, aolp] = &feeds;
+        s.recorder
+            .overlays([&f0.mat, &f1.mat, &f2.mat, &dolp.mat, &aolp.mat], s.frame);
+        all_feed_windows(ui, renderer, &mut feeds, img_size, &mut s);
+        if !s.alignment.feed.mat.empty() {
+            ui.window("alignment")
+                .size([0., 0.], im::Condition::Always)
+                .content_size(img_size.to_array())
+                .build(|| {
+                    s.alignment.feed.make(renderer, img_size).build(ui);
+                });
+        }
 
         ui.window("Control Panel")
             .content_size([500., 500.])
-            .build(|| control_panel(&ui, &mut s, &mut feeds, &mut mini));
+            .build(|| control_panel(&ui, &mut s, &mut feeds));
+        s.plots.window(ui, &s.rois);
+        s.line_profile.window(ui);
     });
 }
 
@@ -99,13 +248,41 @@ fn get_cameras(dual_camera: bool) -> Cameras {
     }
 }
 
+/// the mirror arrangement of the two camera setup, as-is for a single camera
+fn default_orientation(single_camera: bool) -> [orientation::Orientation; 2] {
+    use orientation::Orientation;
+    if single_camera {
+        return Default::default();
+    }
+    [
+        Orientation {
+            flip_h: true,
+            flip_v: true,
+            ..Default::default()
+        },
+        Orientation {
+            flip_v: true,
+            ..Default::default()
+        },
+    ]
+}
+
 /// returns true if all cameras are read successfully
-fn read_cameras(cameras: &mut Cameras, feeds: &mut Feeds, dual_camera: bool) -> bool {
+fn read_cameras(
+    cameras: &mut Cameras,
+    feeds: &mut Feeds,
+    dual_camera: bool,
+    orientation: &[orientation::Orientation; 2],
+) -> bool {
     (0..cameras.len())
         .map(|n| {
-            cameras[[0, n][dual_camera as usize]]
+            let read = cameras[[0, n][dual_camera as usize]]
                 .read(&mut feeds[n].mat)
-                .unwrap()
+                .unwrap();
+            if read {
+                orientation[n].apply(&mut feeds[n].mat);
+            }
+            read
         })
         .all(|x| x)
 }
@@ -123,43 +300,33 @@ fn shift_cameras(s: &State, mat: &mut Mat) {
 }
 
 fn get_detections(
-    channel: &Channel<Mat, Detections>,
-    detections: &mut Option<Detections>,
-    first_sent: &mut bool,
     feeds: &mut Feeds,
-    classes: &mut Option<Classes>,
+    detector: &mut detection::Detector,
+    polarimetry: &polarimetry::Polarimetry,
+    frame: u64,
 ) {
-    if channel
-        .send_on_receive(|det| {
-            *detections = Some(det);
-            feeds[0].mat.clone()
-        })
-        .is_err()
-    {
-        if !*first_sent {
-            *first_sent = false;
-            let _ = channel.0.send(feeds[0].mat.clone());
+    detector.update(&feeds[[0, 1, 5][detector.source]].mat, frame);
+    detector.measure(&feeds[2].mat, polarimetry);
+    for (n, target) in [0, 1, 2, 5, 6].into_iter().enumerate() {
+        if detector.targets[n] && !feeds[target].mat.empty() {
+            detector.draw(&mut feeds[target].mat);
         }
     }
-    if let Some(ref det) = detections {
-        detection::draw(&mut feeds[2].mat, &det, classes);
-    }
 }
 
-fn draw_rois(s: &State, feeds: &mut Feeds, minis: &mut [Rect; 2]) {
-    let size = feeds[0].mat.size().unwrap();
-    let (wsize, shift) = (s.win_size, s.win_shift);
-    let [x, y] = [(size.width - wsize) / 2, (size.height - wsize) / 2];
-
-    let mini_center = Rect::new(x, y, wsize, wsize);
-    let mini_left = Rect::new(x + shift[0], y + shift[1], wsize, wsize);
-
-    for feed in feeds {
-        imgproc::rectangle_def(&mut feed.mat, mini_left, [0., 0., 0., 255.].into()).unwrap();
-        imgproc::rectangle_def(&mut feed.mat, mini_center, [0., 0., 0., 255.].into()).unwrap();
+fn draw_overlays(s: &State, feeds: &mut Feeds) {
+    for (t, n) in [0, 1, 2, 5, 6].into_iter().enumerate() {
+        if !feeds[n].mat.empty() {
+            if s.glare.targets[t] {
+                s.glare.draw(&mut feeds[n].mat);
+            }
+            if s.motion.targets[t] {
+                s.motion.draw(&mut feeds[n].mat);
+            }
+            s.rois.draw(&mut feeds[n].mat);
+            s.line_profile.draw(&mut feeds[n].mat);
+        }
     }
-
-    *minis = [mini_left, mini_center];
 }
 
 fn all_feed_windows(
@@ -167,13 +334,31 @@ fn all_feed_windows(
     renderer: &mut window::AutoRenderer,
     feeds: &mut Feeds,
     img_size: Size,
+    s: &mut State,
 ) {
-    for n in 0..3 {
-        ui.window(["left", "right", "subtracted"][n])
+    let hover = s.inspector.hover.take();
+    for (n, title) in [
+        (0, "left"),
+        (1, "right"),
+        (2, "subtracted"),
+        (5, "DoLP"),
+        (6, "AoLP"),
+    ] {
+        if feeds[n].mat.empty() {
+            continue;
+        }
+        ui.window(title)
             .size([0., 0.], im::Condition::Always)
             .content_size(img_size.to_array())
             .build(|| {
                 feeds[n].make(renderer, img_size).build(ui);
+                let canvas = feeds[n].canvas(ui, n, img_size);
+                s.inspector.interact(ui, &canvas, hover);
+                if s.rois.tool == roi::LINE_TOOL {
+                    s.line_profile.interact(ui, &canvas);
+                } else {
+                    s.rois.interact(ui, &canvas);
+                }
             });
     }
     {
@@ -188,18 +373,28 @@ fn all_feed_windows(
                 .content_size(img_size.to_array())
                 .build(|| {
                     feed.make(renderer, img_size).build(ui);
+                    let canvas = feed.canvas(ui, 7 + n, img_size);
+                    s.inspector.interact(ui, &canvas, hover);
                 });
         }
     }
 }
 
-fn control_panel(ui: &&mut window::Ui, s: &mut State, feeds: &mut Feeds, minis: &mut [Rect; 2]) {
+fn control_panel(ui: &&mut window::Ui, s: &mut State, feeds: &mut Feeds) {
+    profile_panel(ui, s);
     ui.slider("image base size", 1, 400, &mut s.base_px);
 
+    ui.text("orientation:");
+    for n in 0..2 {
+        s.orientation[n].panel(ui, ["left", "right"][n]);
+    }
+
     ui.text("calibration:");
     ui.slider("camera 2 shift x", -400, 400, &mut s.cam_shift[0]);
     ui.slider("camera 2 shift y", -400, 400, &mut s.cam_shift[1]);
     ui.slider("window size", 1, 200, &mut s.win_size);
+    ui.slider("left roi shift x", -400, 400, &mut s.seed_shift[0]);
+    ui.slider("left roi shift y", -400, 400, &mut s.seed_shift[1]);
 
     if ui.button("auto calibrate") {
         calibrate::get_shift(&feeds[0].mat, &feeds[1].mat, s.win_size, &mut s.cam_shift);
@@ -208,70 +403,141 @@ fn control_panel(ui: &&mut window::Ui, s: &mut State, feeds: &mut Feeds, minis:
     if ui.button("reset") {
         s.cam_shift = [0, 0];
     };
+    s.alignment.panel(ui);
+    s.polarimetry.panel(ui);
 
     if ui.button("save null") {
-        feeds[5 - 2].mat.set(feeds[0].mat.clone()).unwrap();
-        feeds[5 - 1].mat.set(feeds[1].mat.clone()).unwrap();
+        feeds[3].mat.set(feeds[0].mat.clone()).unwrap();
+        feeds[4].mat.set(feeds[1].mat.clone()).unwrap();
     }
     ui.same_line();
     if ui.button("reset null") {
-        feeds[5 - 2].mat.set(Mat::default()).unwrap();
-        feeds[5 - 1].mat.set(Mat::default()).unwrap();
+        feeds[3].mat.set(Mat::default()).unwrap();
+        feeds[4].mat.set(Mat::default()).unwrap();
     }
 
     ui.text("save:");
     for n in 0..3 {
         ui.same_line();
         if ui.button(format!("feed {}", n + 1)) {
-            imgcodecs::imwrite_def(
-                &utils::get_save_filepath(&format!("f{}.png", n + 1)),
-                &feeds[n].mat,
-            )
-            .unwrap();
+            let filepath = utils::get_save_filepath(&format!("f{}.png", n + 1));
+            imgcodecs::imwrite_def(&filepath, &feeds[n].mat).unwrap();
+            if s.detector.log.coco {
+                let (size, objects) = (feeds[n].mat.size().unwrap(), &s.detector.objects);
+                s.detector.status =
+                    match annotations::write_coco(&filepath, size, objects, &s.detector.classes) {
+                        Ok(path) => format!("saved {}", path),
+                        Err(err) => format!("COCO export error: {}", err),
+                    };
+            }
         };
     }
 
-    ui.text("recording:");
-    ui.same_line();
-    match s.writer {
-        Some(ref mut w) => {
-            w.write(&feeds[2].mat).unwrap();
-            if ui.button("stop") {
-                s.writer = None;
+    s.recorder.panel(ui);
+    s.logger.panel(ui);
+    s.detector.panel(ui);
+    s.glare.panel(ui);
+    s.motion.panel(ui);
+
+    ui.new_line();
+    ui.text("regions of interest:");
+    s.rois.panel(ui);
+    s.rois.table(ui);
+}
+
+fn profile_panel(ui: &window::Ui, s: &mut State) {
+    ui.input_text("profile", &mut s.profile).build();
+    if ui.button("save profile") {
+        s.profile_status = match profile::save(&s.profile, s) {
+            Ok(()) => {
+                s.active_profile = s.profile.clone();
+                format!("saved {}", profile::get_filepath(&s.profile))
             }
-        }
-        None => {
-            if ui.button("start") {
-                let filepath = utils::get_save_filepath("out.mp4");
-                let avc1 = videoio::VideoWriter::fourcc('a', 'v', 'c', '1').unwrap();
-                let (fps, size) = (15., feeds[2].mat.size().unwrap());
-                let writer = videoio::VideoWriter::new(&filepath, avc1, fps, size, true).unwrap();
-                s.writer = Some(writer);
+            Err(err) => err.message,
+        };
+    }
+    ui.same_line();
+    if let Some(_combo) = ui.begin_combo("##profiles", "load profile") {
+        for name in profile::list() {
+            if ui.selectable(&name) {
+                s.profile_status = match profile::load(&name, s) {
+                    Ok(()) => {
+                        s.active_profile = name.clone();
+                        format!("loaded {}", name)
+                    }
+                    Err(err) => err.message,
+                };
+                s.profile = name;
             }
         }
     }
-    ui.slider("left window x", -400, 400, &mut s.win_shift[0]);
-    ui.slider("left window y", -400, 400, &mut s.win_shift[1]);
-    for (a, mini) in minis.into_iter().enumerate() {
-        ui.new_line();
-        ui.text(["left", "center"][a]);
-        if let Some(_) = ui.begin_table_header(
-            "channel readings",
-            ["feeds", "red", "green", "blue"].map(|h| im::TableColumnSetup::new(h)),
-        ) {
-            for n in 0..3 {
-                let mut bgr = Vector::<Mat>::new();
-                split(&feeds[n].mat.roi(*mini).unwrap(), &mut bgr).unwrap();
-                ui.table_next_column();
-                ui.text(format!("camera {}", n + 1));
-                for i in 0..3 {
-                    ui.table_next_column();
-                    ui.text(format!(
-                        "{:.2}",
-                        mean_def(&bgr.get(2 - i).unwrap()).unwrap()[0]
-                    ));
-                }
-            }
+    if !s.profile_status.is_empty() {
+        ui.text(&s.profile_status);
+    }
+}
+
+impl profile::Persist for State {
+    fn save(&self, storage: &mut FileStorage) -> Result<()> {
+        storage.write_i32("base_px", self.base_px)?;
+        storage.write_i32("win_size", self.win_size)?;
+        storage.write_i32("cam_shift_x", self.cam_shift[0])?;
+        storage.write_i32("cam_shift_y", self.cam_shift[1])?;
+        storage.write_i32("seed_shift_x", self.seed_shift[0])?;
+        storage.write_i32("seed_shift_y", self.seed_shift[1])?;
+        profile::save_struct(storage, "left_orientation", &self.orientation[0])?;
+        profile::save_struct(storage, "right_orientation", &self.orientation[1])?;
+        profile::save_struct(storage, "polarimetry", &self.polarimetry)?;
+        profile::save_struct(storage, "detection", &self.detector)?;
+        profile::save_struct(storage, "glare", &self.glare)?;
+        profile::save_struct(storage, "motion", &self.motion)?;
+        profile::save_struct(storage, "rois", &self.rois)?;
+        profile::save_struct(storage, "recording", &self.recorder)
+    }
+
+    fn load(&mut self, node: &FileNode) -> Result<()> {
+        profile::read_i32(node, "base_px", &mut self.base_px)?;
+        profile::read_i32(node, "win_size", &mut self.win_size)?;
+        profile::read_i32(node, "cam_shift_x", &mut self.cam_shift[0])?;
+        profile::read_i32(node, "cam_shift_y", &mut self.cam_shift[1])?;
+        profile::read_i32(node, "seed_shift_x", &mut self.seed_shift[0])?;
+        profile::read_i32(node, "seed_shift_y", &mut self.seed_shift[1])?;
+        profile::load_struct(node, "left_orientation", &mut self.orientation[0])?;
+        profile::load_struct(node, "right_orientation", &mut self.orientation[1])?;
+        profile::load_struct(node, "polarimetry", &mut self.polarimetry)?;
+        profile::load_struct(node, "detection", &mut self.detector)?;
+        self.detector.sync();
+        profile::load_struct(node, "glare", &mut self.glare)?;
+        profile::load_struct(node, "motion", &mut self.motion)?;
+        profile::load_struct(node, "rois", &mut self.rois)?;
+        profile::load_struct(node, "recording", &mut self.recorder)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_orientation_matches_the_two_camera_flips() {
+        let values: Vec<u8> = (0..12).collect();
+        let mat = Mat::new_rows_cols_with_data(3, 4, &values)
+            .unwrap()
+            .try_clone()
+            .unwrap();
+        let orientation = default_orientation(false);
+        for (n, code) in [-1, 0].into_iter().enumerate() {
+            let (mut oriented, mut flipped) = (mat.clone(), Mat::default());
+            orientation[n].apply(&mut oriented);
+            flip(&mat, &mut flipped, code).unwrap();
+            assert_eq!(
+                oriented.data_bytes().unwrap(),
+                flipped.data_bytes().unwrap()
+            );
+        }
+        for orientation in default_orientation(true) {
+            let mut oriented = mat.clone();
+            orientation.apply(&mut oriented);
+            assert_eq!(oriented.data_bytes().unwrap(), mat.data_bytes().unwrap());
         }
     }
 }
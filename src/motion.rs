@@ -0,0 +1,286 @@
+use crate::profile::{self, Persist};
+use crate::*;
+use opencv::video::{
+    self, BackgroundSubtractorKNNTrait, BackgroundSubtractorMOG2Trait, BackgroundSubtractorTrait,
+};
+
+/// background models, index of `Motion::method`
+pub const METHODS: [&str; 2] = ["MOG2", "KNN"];
+/// default foreground threshold of each method: variance for MOG2, squared distance for KNN
+const THRESHOLDS: [f32; 2] = [16., 400.];
+/// shadows are marked at half intensity in the foreground mask
+const SHADOW: f64 = 127.;
+
+enum Subtractor {
+    Mog2(Ptr<video::BackgroundSubtractorMOG2>),
+    Knn(Ptr<video::BackgroundSubtractorKNN>),
+}
+
+impl Subtractor {
+    fn apply(&mut self, frame: &Mat, mask: &mut Mat, learning_rate: f64) {
+        match self {
+            Self::Mog2(s) => BackgroundSubtractorTrait::apply(s, frame, mask, learning_rate),
+            Self::Knn(s) => BackgroundSubtractorTrait::apply(s, frame, mask, learning_rate),
+        }
+        .unwrap();
+    }
+
+    /// changes the settings in place, keeping the learned background
+    fn configure(&mut self, history: i32, threshold: f64, shadows: bool) {
+        match self {
+            Self::Mog2(s) => {
+                s.set_history(history).unwrap();
+                s.set_var_threshold(threshold).unwrap();
+                s.set_detect_shadows(shadows).unwrap();
+            }
+            Self::Knn(s) => {
+                s.set_history(history).unwrap();
+                s.set_dist2_threshold(threshold).unwrap();
+                s.set_detect_shadows(shadows).unwrap();
+            }
+        }
+    }
+}
+
+/// adaptive background model of one feed, and the blobs that move against it
+pub struct Motion {
+    pub enabled: bool,
+    /// index into `METHODS`
+    pub method: usize,
+    /// index into `detection::TARGETS`
+    pub source: usize,
+    /// frames the background model remembers
+    pub history: i32,
+    pub threshold: f32,
+    pub shadows: bool,
+    /// fraction of the background replaced per frame, negative to let the model choose
+    pub learning_rate: f32,
+    /// side of the opening kernel against speckle
+    pub kernel: i32,
+    pub min_area: f32,
+    pub show_mask: bool,
+    /// in the order of `detection::TARGETS`
+    pub targets: [bool; 5],
+    /// records the subtracted feed while there is motion
+    pub trigger: bool,
+    /// frames recorded on after the motion stops
+    pub hold: i32,
+    pub blobs: Vec<Rect>,
+    /// 255 on the foreground of the last frame
+    pub mask: Mat,
+    /// frames since the last motion, `u64::MAX` before any
+    pub quiet: u64,
+    subtractor: Option<Subtractor>,
+    /// method, history, threshold and shadows the subtractor was last given
+    built: (usize, i32, f32, bool),
+}
+
+impl Default for Motion {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            method: 0,
+            source: 2,
+            history: 500,
+            threshold: THRESHOLDS[0],
+            shadows: true,
+            learning_rate: -1.,
+            kernel: 3,
+            min_area: 100.,
+            show_mask: true,
+            targets: [false, false, true, false, false],
+            trigger: false,
+            hold: 45,
+            blobs: Vec::new(),
+            mask: Mat::default(),
+            quiet: u64::MAX,
+            subtractor: None,
+            built: (0, 0, 0., false),
+        }
+    }
+}
+
+impl Motion {
+    pub fn moving(&self) -> bool {
+        !self.blobs.is_empty()
+    }
+
+    /// the trigger wants the recording to run
+    pub fn recording(&self) -> bool {
+        self.enabled && self.trigger && self.quiet <= self.hold as u64
+    }
+
+    fn subtractor(&mut self) -> &mut Subtractor {
+        let settings = (self.method, self.history, self.threshold, self.shadows);
+        let (history, threshold) = (self.history, self.threshold as f64);
+        if let Some(ref mut subtractor) = self.subtractor {
+            // only a new method needs a new model, the rest is set on the learned one
+            if self.built.0 == self.method && self.built != settings {
+                subtractor.configure(history, threshold, self.shadows);
+                self.built = settings;
+            }
+        }
+        if self.subtractor.is_none() || self.built != settings {
+            self.subtractor = Some(match self.method {
+                0 => Subtractor::Mog2(
+                    video::create_background_subtractor_mog2(history, threshold, self.shadows)
+                        .unwrap(),
+                ),
+                _ => Subtractor::Knn(
+                    video::create_background_subtractor_knn(history, threshold, self.shadows)
+                        .unwrap(),
+                ),
+            });
+            self.built = settings;
+        }
+        self.subtractor.as_mut().unwrap()
+    }
+
+    /// learns the frame into the background and finds what moves against it
+    pub fn update(&mut self, frame: &Mat) {
+        self.blobs.clear();
+        if !self.enabled || frame.empty() {
+            self.mask = Mat::default();
+            return;
+        }
+        let (mut foreground, mut mask) = (Mat::default(), Mat::default());
+        let learning_rate = self.learning_rate as f64;
+        self.subtractor()
+            .apply(frame, &mut foreground, learning_rate);
+        imgproc::threshold(&foreground, &mut mask, SHADOW, 255., imgproc::THRESH_BINARY).unwrap();
+        if self.kernel > 1 {
+            let ksize = Size::new(self.kernel, self.kernel);
+            let kernel =
+                imgproc::get_structuring_element_def(imgproc::MORPH_ELLIPSE, ksize).unwrap();
+            imgproc::morphology_ex_def(&mask.clone(), &mut mask, imgproc::MORPH_OPEN, &kernel)
+                .unwrap();
+        }
+
+        let mut contours = Vector::<Vector<Point>>::new();
+        imgproc::find_contours_def(
+            &mask,
+            &mut contours,
+            imgproc::RETR_EXTERNAL,
+            imgproc::CHAIN_APPROX_SIMPLE,
+        )
+        .unwrap();
+        for contour in contours {
+            if imgproc::contour_area_def(&contour).unwrap() >= self.min_area as f64 {
+                self.blobs.push(imgproc::bounding_rect(&contour).unwrap());
+            }
+        }
+        self.quiet = match self.moving() {
+            true => 0,
+            false => self.quiet.saturating_add(1),
+        };
+        self.mask = mask;
+    }
+
+    pub fn draw(&self, mat: &mut Mat) {
+        let color: Scalar = [0., 255., 0., 255.].into();
+        if self.show_mask && !self.mask.empty() && self.mask.size().unwrap() == mat.size().unwrap()
+        {
+            let mut tinted = mat.clone();
+            tinted.set_to(&color, &self.mask).unwrap();
+            add_weighted_def(&mat.clone(), 0.6, &tinted, 0.4, 0., mat).unwrap();
+        }
+        for blob in &self.blobs {
+            imgproc::rectangle_def(mat, *blob, color).unwrap();
+        }
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui) {
+        if ui.checkbox("motion detection", &mut self.enabled) {
+            // no recording until something moves
+            self.quiet = u64::MAX;
+        }
+        if !self.enabled {
+            return;
+        }
+        ui.same_line();
+        if ui.button("reset background") {
+            self.subtractor = None;
+        }
+        if ui.combo_simple_string("background model", &mut self.method, &METHODS) {
+            self.threshold = THRESHOLDS[self.method];
+        }
+        ui.combo_simple_string("motion on", &mut self.source, &TARGETS);
+        ui.slider("history", 10, 5000, &mut self.history);
+        ui.slider("foreground threshold", 1., 1000., &mut self.threshold);
+        ui.checkbox("detect shadows", &mut self.shadows);
+        ui.slider("learning rate (<0 auto)", -1., 0.1, &mut self.learning_rate);
+        ui.slider("motion kernel", 1, 31, &mut self.kernel);
+        ui.slider("motion min area", 0., 5000., &mut self.min_area);
+        ui.checkbox("show foreground", &mut self.show_mask);
+        ui.text("draw on:");
+        for (n, name) in TARGETS.iter().enumerate() {
+            ui.same_line();
+            ui.checkbox(format!("{}##motion target", name), &mut self.targets[n]);
+        }
+        if ui.checkbox("record on motion", &mut self.trigger) {
+            self.quiet = u64::MAX;
+        }
+        if self.trigger {
+            ui.same_line();
+            ui.set_next_item_width(120.);
+            ui.slider("hold frames", 0, 600, &mut self.hold);
+        }
+        match (self.moving(), self.quiet) {
+            (true, _) => ui.text(format!("{} moving blobs", self.blobs.len())),
+            (false, u64::MAX) => ui.text("no motion yet"),
+            (false, quiet) => ui.text(format!("still for {} frames", quiet)),
+        }
+    }
+}
+
+impl Persist for Motion {
+    fn save(&self, storage: &mut FileStorage) -> Result<()> {
+        storage.write_i32("enabled", self.enabled as i32)?;
+        storage.write_i32("method", self.method as i32)?;
+        storage.write_i32("source", self.source as i32)?;
+        storage.write_i32("history", self.history)?;
+        storage.write_f64("threshold", self.threshold as f64)?;
+        storage.write_i32("shadows", self.shadows as i32)?;
+        storage.write_f64("learning_rate", self.learning_rate as f64)?;
+        storage.write_i32("kernel", self.kernel)?;
+        storage.write_f64("min_area", self.min_area as f64)?;
+        storage.write_i32("show_mask", self.show_mask as i32)?;
+        let targets = (0..TARGETS.len()).filter(|n| self.targets[*n]);
+        storage.write_i32("targets", targets.map(|n| 1 << n).sum())?;
+        storage.write_i32("trigger", self.trigger as i32)?;
+        storage.write_i32("hold", self.hold)
+    }
+
+    fn load(&mut self, node: &FileNode) -> Result<()> {
+        profile::read_bool(node, "enabled", &mut self.enabled)?;
+        for (key, value, len) in [
+            ("method", &mut self.method, METHODS.len()),
+            ("source", &mut self.source, TARGETS.len()),
+        ] {
+            let mut index = *value as i32;
+            profile::read_i32(node, key, &mut index)?;
+            *value = (index.max(0) as usize).min(len - 1);
+        }
+        profile::read_i32(node, "history", &mut self.history)?;
+        for (key, value) in [
+            ("threshold", &mut self.threshold),
+            ("learning_rate", &mut self.learning_rate),
+            ("min_area", &mut self.min_area),
+        ] {
+            let mut float = *value as f64;
+            profile::read_f64(node, key, &mut float)?;
+            *value = float as f32;
+        }
+        profile::read_bool(node, "shadows", &mut self.shadows)?;
+        profile::read_i32(node, "kernel", &mut self.kernel)?;
+        profile::read_bool(node, "show_mask", &mut self.show_mask)?;
+        let mut targets = -1;
+        profile::read_i32(node, "targets", &mut targets)?;
+        if targets >= 0 {
+            self.targets = std::array::from_fn(|n| targets & 1 << n != 0);
+        }
+        profile::read_bool(node, "trigger", &mut self.trigger)?;
+        self.quiet = u64::MAX;
+        profile::read_i32(node, "hold", &mut self.hold)
+    }
+}
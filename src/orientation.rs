@@ -0,0 +1,62 @@
+use crate::profile::{self, Persist};
+use crate::*;
+
+pub const ROTATIONS: [&str; 4] = ["0", "90", "180", "270"];
+
+/// per-source transform, applied in this order: transpose, clockwise rotation, flips
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Orientation {
+    pub flip_h: bool,
+    pub flip_v: bool,
+    /// clockwise quarter turns
+    pub rotate: usize,
+    pub transpose: bool,
+}
+
+impl Orientation {
+    pub fn apply(&self, mat: &mut Mat) {
+        if self.transpose {
+            transpose(&mat.clone(), mat).unwrap();
+        }
+        if self.rotate != 0 {
+            let code = [ROTATE_90_CLOCKWISE, ROTATE_180, ROTATE_90_COUNTERCLOCKWISE];
+            rotate(&mat.clone(), mat, code[self.rotate - 1]).unwrap();
+        }
+        match (self.flip_h, self.flip_v) {
+            (true, true) => flip(&mat.clone(), mat, -1).unwrap(),
+            (true, false) => flip(&mat.clone(), mat, 1).unwrap(),
+            (false, true) => flip(&mat.clone(), mat, 0).unwrap(),
+            (false, false) => {}
+        }
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui, label: &str) {
+        let _id = ui.push_id(label);
+        ui.text(label);
+        ui.same_line();
+        ui.checkbox("flip H", &mut self.flip_h);
+        ui.same_line();
+        ui.checkbox("flip V", &mut self.flip_v);
+        ui.same_line();
+        ui.checkbox("transpose", &mut self.transpose);
+        ui.combo_simple_string("rotate", &mut self.rotate, &ROTATIONS);
+    }
+}
+
+impl Persist for Orientation {
+    fn save(&self, storage: &mut FileStorage) -> Result<()> {
+        storage.write_i32("flip_h", self.flip_h as i32)?;
+        storage.write_i32("flip_v", self.flip_v as i32)?;
+        storage.write_i32("rotate", self.rotate as i32)?;
+        storage.write_i32("transpose", self.transpose as i32)
+    }
+
+    fn load(&mut self, node: &FileNode) -> Result<()> {
+        profile::read_bool(node, "flip_h", &mut self.flip_h)?;
+        profile::read_bool(node, "flip_v", &mut self.flip_v)?;
+        let mut rotate = self.rotate as i32;
+        profile::read_i32(node, "rotate", &mut rotate)?;
+        self.rotate = rotate.rem_euclid(4) as usize;
+        profile::read_bool(node, "transpose", &mut self.transpose)
+    }
+}
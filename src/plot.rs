@@ -0,0 +1,289 @@
+use crate::*;
+use std::collections::VecDeque;
+use std::time::Instant;
+
+/// longest history the slider offers, a day
+const MAX_HISTORY: f32 = 86400.;
+
+pub const METRICS: [&str; 11] = [
+    "left red",
+    "left green",
+    "left blue",
+    "right red",
+    "right green",
+    "right blue",
+    "subtracted red",
+    "subtracted green",
+    "subtracted blue",
+    "DoLP",
+    "AoLP",
+];
+
+const COLORS: [[f32; 4]; 6] = [
+    [1., 0.4, 0.4, 1.],
+    [0.4, 1., 0.4, 1.],
+    [0.4, 0.6, 1., 1.],
+    [1., 1., 0.4, 1.],
+    [1., 0.4, 1., 1.],
+    [0.4, 1., 1., 1.],
+];
+
+/// mean of one of `METRICS` from the statistics of a roi
+pub fn metric_value(metric: usize, stats: &stats::RoiStats) -> Option<f64> {
+    match metric {
+        0..=8 => Some(stats.feeds[metric / 3][2 - metric % 3].mean),
+        9 => stats.polar.map(|p| p.dolp_mean),
+        _ => stats.polar.map(|p| p.aolp_mean),
+    }
+}
+
+pub struct Series {
+    /// `roi::Roi::id`, so the history survives renames
+    pub roi: usize,
+    pub metric: usize,
+    /// (seconds, value)
+    pub samples: VecDeque<[f64; 2]>,
+}
+
+impl Series {
+    pub fn label(&self, rois: &roi::Rois) -> String {
+        let name = rois
+            .get(self.roi)
+            .map_or("deleted roi", |roi| roi.name.as_str());
+        format!("{} {}", name, METRICS[self.metric])
+    }
+}
+
+#[derive(Default)]
+pub struct Plot {
+    pub series: Vec<Series>,
+    /// roi list index and metric picked for the next series
+    new_roi: usize,
+    new_metric: usize,
+}
+
+/// a line of a plot, in data coordinates
+pub struct Line {
+    pub label: String,
+    pub color: [f32; 4],
+    pub points: Vec<[f64; 2]>,
+}
+
+/// history of roi readings over time
+pub struct Plots {
+    pub plots: Vec<Plot>,
+    /// seconds of samples kept for every series
+    pub history: f32,
+    /// seconds shown
+    pub window: f32,
+    /// seconds the view is scrolled back
+    pub scroll: f32,
+    /// time the view was frozen at
+    pub paused: Option<f64>,
+    pub autoscale: bool,
+    pub range: [f32; 2],
+    pub markers: bool,
+    start: Instant,
+}
+
+impl Default for Plots {
+    fn default() -> Self {
+        Self {
+            plots: vec![Plot::default()],
+            history: 3600.,
+            window: 30.,
+            scroll: 0.,
+            paused: None,
+            autoscale: true,
+            range: [0., 255.],
+            markers: false,
+            start: Instant::now(),
+        }
+    }
+}
+
+impl Plots {
+    pub fn record(&mut self, rois: &roi::Rois) {
+        let t = self.start.elapsed().as_secs_f64();
+        for series in self.plots.iter_mut().flat_map(|p| &mut p.series) {
+            let reading = rois
+                .list
+                .iter()
+                .zip(&rois.readings)
+                .find(|(roi, _)| roi.id == series.roi);
+            if let Some(value) = reading.and_then(|(_, r)| metric_value(series.metric, r)) {
+                series.samples.push_back([t, value]);
+            }
+            let history = self.history as f64;
+            while series.samples.front().is_some_and(|s| t - s[0] > history) {
+                series.samples.pop_front();
+            }
+        }
+    }
+
+    pub fn window(&mut self, ui: &imgui::Ui, rois: &roi::Rois) {
+        ui.window("plots")
+            .size([500., 400.], im::Condition::FirstUseEver)
+            .build(|| self.contents(ui, rois));
+    }
+
+    fn contents(&mut self, ui: &imgui::Ui, rois: &roi::Rois) {
+        let now = self.start.elapsed().as_secs_f64();
+        ui.slider_config("history kept (s)", 10., MAX_HISTORY)
+            .flags(imgui::SliderFlags::LOGARITHMIC)
+            .build(&mut self.history);
+        self.window = self.window.min(self.history);
+        ui.slider("window length (s)", 1., self.history, &mut self.window);
+        let history = (now as f32 - self.window).max(0.);
+        ui.slider("scroll back (s)", 0., history, &mut self.scroll);
+        let mut paused = self.paused.is_some();
+        if ui.checkbox("pause", &mut paused) {
+            self.paused = paused.then_some(now);
+        }
+        ui.same_line();
+        ui.checkbox("markers", &mut self.markers);
+        ui.same_line();
+        ui.checkbox("autoscale", &mut self.autoscale);
+        if !self.autoscale {
+            ui.input_float2("y range", &mut self.range).build();
+        }
+
+        let end = self.paused.unwrap_or(now) - self.scroll as f64;
+        let x = [end - self.window as f64, end];
+        let y = (!self.autoscale).then_some([self.range[0] as f64, self.range[1] as f64]);
+
+        let names: Vec<&str> = rois.list.iter().map(|r| r.name.as_str()).collect();
+        let mut remove_plot = None;
+        for (p, plot) in self.plots.iter_mut().enumerate() {
+            let _id = ui.push_id_usize(p);
+            ui.separator();
+            if !names.is_empty() {
+                ui.set_next_item_width(120.);
+                ui.combo_simple_string("##roi", &mut plot.new_roi, &names);
+                ui.same_line();
+                ui.set_next_item_width(150.);
+                ui.combo_simple_string("##metric", &mut plot.new_metric, &METRICS);
+                ui.same_line();
+                if ui.button("add series") {
+                    plot.series.push(Series {
+                        roi: rois.list[plot.new_roi.min(names.len() - 1)].id,
+                        metric: plot.new_metric,
+                        samples: VecDeque::new(),
+                    });
+                }
+                ui.same_line();
+            }
+            if ui.button("remove plot") {
+                remove_plot = Some(p);
+            }
+
+            let lines: Vec<Line> = plot
+                .series
+                .iter()
+                .enumerate()
+                .map(|(n, series)| Line {
+                    label: series.label(rois),
+                    color: COLORS[n % COLORS.len()],
+                    points: series
+                        .samples
+                        .iter()
+                        .filter(|s| s[0] >= x[0] && s[0] <= x[1])
+                        .copied()
+                        .collect(),
+                })
+                .collect();
+            draw(
+                ui,
+                [ui.content_region_avail()[0], 150.],
+                &lines,
+                x,
+                y,
+                self.markers,
+            );
+
+            let mut remove_series = None;
+            for (n, line) in lines.iter().enumerate() {
+                let _id = ui.push_id_usize(n);
+                if ui.small_button("x") {
+                    remove_series = Some(n);
+                }
+                ui.same_line();
+                ui.text_colored(line.color, &line.label);
+            }
+            if let Some(n) = remove_series {
+                plot.series.remove(n);
+            }
+        }
+        if let Some(p) = remove_plot {
+            self.plots.remove(p);
+        }
+        ui.separator();
+        if ui.button("add plot") {
+            self.plots.push(Plot::default());
+        }
+    }
+}
+
+/// draws the lines over the x range, and the y range or the data range if none
+pub fn draw(
+    ui: &imgui::Ui,
+    size: [f32; 2],
+    lines: &[Line],
+    x: [f64; 2],
+    y: Option<[f64; 2]>,
+    markers: bool,
+) {
+    let y = y.unwrap_or_else(|| {
+        let values = lines.iter().flat_map(|l| l.points.iter().map(|p| p[1]));
+        let (min, max) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), v| {
+            (min.min(v), max.max(v))
+        });
+        match (min.is_finite(), max - min) {
+            (false, _) => [0., 1.],
+            (true, d) if d < 1e-9 => [min - 0.5, max + 0.5],
+            (true, d) => [min - 0.05 * d, max + 0.05 * d],
+        }
+    });
+
+    let origin = ui.cursor_screen_pos();
+    let end = [origin[0] + size[0], origin[1] + size[1]];
+    ui.dummy(size);
+
+    let draw_list = ui.get_window_draw_list();
+    draw_list
+        .add_rect(origin, end, [0.1, 0.1, 0.1, 1.])
+        .filled(true)
+        .build();
+    let to_screen = |p: &[f64; 2]| {
+        [
+            origin[0] + ((p[0] - x[0]) / (x[1] - x[0]).max(1e-9)) as f32 * size[0],
+            end[1] - ((p[1] - y[0]) / (y[1] - y[0]).max(1e-9)) as f32 * size[1],
+        ]
+    };
+    draw_list.with_clip_rect_intersect(origin, end, || {
+        for line in lines {
+            let points: Vec<[f32; 2]> = line.points.iter().map(to_screen).collect();
+            if markers {
+                for p in &points {
+                    draw_list
+                        .add_circle(*p, 2., line.color)
+                        .filled(true)
+                        .build();
+                }
+            }
+            if points.len() > 1 {
+                draw_list.add_polyline(points, line.color).build();
+            }
+        }
+    });
+
+    let grey = [0.7, 0.7, 0.7, 1.];
+    draw_list.add_text([origin[0] + 2., origin[1]], grey, format!("{:.3}", y[1]));
+    draw_list.add_text([origin[0] + 2., end[1] - 16.], grey, format!("{:.3}", y[0]));
+    let x_label = format!("{:.1} .. {:.1}", x[0], x[1]);
+    draw_list.add_text(
+        [end[0] - 8. * x_label.len() as f32, end[1] - 16.],
+        grey,
+        x_label,
+    );
+}
@@ -0,0 +1,269 @@
+use crate::profile::{self, Persist};
+use crate::*;
+
+/// analyser of one channel, as mounted
+#[derive(Clone, Copy, Debug)]
+pub struct Analyser {
+    /// transmission axis in degrees
+    pub angle: f64,
+    /// min / max transmission, 0 for an ideal polarizer
+    pub extinction: f64,
+}
+
+impl Analyser {
+    /// diattenuation, 1 for an ideal polarizer
+    pub fn diattenuation(&self) -> f64 {
+        (1. - self.extinction) / (1. + self.extinction)
+    }
+}
+
+/// Stokes reconstruction from the two analysed channels.
+///
+/// The analysers are calibrated by rotating a reference polarizer (or a known
+/// linearly polarized source) in front of both cameras and fitting
+/// `I(φ) = a + b cos 2φ + c sin 2φ` to the center window of each channel.
+pub struct Polarimetry {
+    pub analysers: [Analyser; 2],
+    /// degree of linear polarization, CV_32F in [0, 1]
+    pub dolp: Mat,
+    /// angle of linear polarization, CV_32F in degrees [0, 180)
+    pub aolp: Mat,
+    /// why DoLP and AoLP are missing, empty while they are computed
+    pub status: String,
+    pub reference_angle: f32,
+    pub step: f32,
+    samples: Vec<(f64, [f64; 2])>,
+    sample_requested: bool,
+}
+
+impl Default for Polarimetry {
+    fn default() -> Self {
+        Self {
+            analysers: [
+                Analyser {
+                    angle: 0.,
+                    extinction: 0.,
+                },
+                Analyser {
+                    angle: 90.,
+                    extinction: 0.,
+                },
+            ],
+            dolp: Mat::default(),
+            aolp: Mat::default(),
+            status: String::new(),
+            reference_angle: 0.,
+            step: 15.,
+            samples: Vec::new(),
+            sample_requested: false,
+        }
+    }
+}
+
+impl Polarimetry {
+    /// analysis matrix, maps the Stokes vector (S0, S1, S2) to the two channel intensities
+    pub fn matrix(&self) -> [[f64; 3]; 2] {
+        self.analysers.map(|a| {
+            let (d, theta) = (a.diattenuation(), (2. * a.angle).to_radians());
+            [0.5, 0.5 * d * theta.cos(), 0.5 * d * theta.sin()]
+        })
+    }
+
+    /// pseudo-inverse of the analysis matrix, maps the two channel intensities to (S0, S1, S2)
+    pub fn inverse(&self) -> Option<[[f64; 2]; 3]> {
+        let a = self.matrix();
+        let dot = |i: usize, j: usize| (0..3).map(|k| a[i][k] * a[j][k]).sum::<f64>();
+        let (p, q, r) = (dot(0, 0), dot(0, 1), dot(1, 1));
+        let det = p * r - q * q;
+        if det.abs() < 1e-9 {
+            return None;
+        }
+        let inv = [[r / det, -q / det], [-q / det, p / det]];
+        Some([0, 1, 2].map(|k| [0, 1].map(|j| a[0][k] * inv[0][j] + a[1][k] * inv[1][j])))
+    }
+
+    /// drops DoLP and AoLP and their feeds, so nothing stale is shown as current
+    pub fn clear(&mut self, feeds: [&mut Mat; 2], status: &str) {
+        self.dolp = Mat::default();
+        self.aolp = Mat::default();
+        for feed in feeds {
+            *feed = Mat::default();
+        }
+        self.status = status.into();
+    }
+
+    /// computes DoLP and AoLP from the registered channels and renders them into the feeds
+    pub fn update(&mut self, mat1: &Mat, mat2: &Mat, window: i32, feeds: [&mut Mat; 2]) {
+        if mat1.empty() {
+            return;
+        }
+        if mat1.size().unwrap() != mat2.size().unwrap() {
+            self.clear(feeds, "the cameras differ in size");
+            return;
+        }
+        let (i1, i2) = (intensity(mat1), intensity(mat2));
+
+        if self.sample_requested {
+            self.sample_requested = false;
+            let size = mat1.size().unwrap();
+            let window = window.min(size.width).min(size.height);
+            let rect = Rect::new(
+                (size.width - window) / 2,
+                (size.height - window) / 2,
+                window,
+                window,
+            );
+            let mean = |i: &Mat| mean_def(&i.roi(rect).unwrap()).unwrap()[0];
+            let sample = [mean(&i1), mean(&i2)];
+            self.samples.push((self.reference_angle as f64, sample));
+            self.reference_angle = (self.reference_angle + self.step).rem_euclid(180.);
+        }
+
+        let Some(inverse) = self.inverse() else {
+            self.clear(feeds, "the analysers cannot be inverted, calibrate them");
+            return;
+        };
+        self.status.clear();
+        let [s0, s1, s2] = inverse.map(|[w1, w2]| {
+            let mut s = Mat::default();
+            add_weighted_def(&i1, w1, &i2, w2, 0., &mut s).unwrap();
+            s
+        });
+
+        let (mut linear, mut s0_safe) = (Mat::default(), Mat::default());
+        magnitude(&s1, &s2, &mut linear).unwrap();
+        add_weighted_def(&s0, 1., &s0, 0., 1e-6, &mut s0_safe).unwrap();
+        divide2_def(&linear, &s0_safe, &mut self.dolp).unwrap();
+        imgproc::threshold(
+            &self.dolp.clone(),
+            &mut self.dolp,
+            1.,
+            1.,
+            imgproc::THRESH_TRUNC,
+        )
+        .unwrap();
+
+        let mut angle = Mat::default();
+        phase(&s1, &s2, &mut angle, true).unwrap();
+        angle.convert_to(&mut self.aolp, CV_32F, 0.5, 0.).unwrap();
+
+        let [dolp_feed, aolp_feed] = feeds;
+        let mut scaled = Mat::default();
+        self.dolp.convert_to(&mut scaled, CV_8U, 255., 0.).unwrap();
+        imgproc::cvt_color_def(&scaled, dolp_feed, imgproc::COLOR_GRAY2BGR).unwrap();
+        self.aolp
+            .convert_to(&mut scaled, CV_8U, 255. / 180., 0.)
+            .unwrap();
+        imgproc::apply_color_map(&scaled, aolp_feed, imgproc::COLORMAP_HSV).unwrap();
+    }
+
+    /// least squares fit of the analyser angle and extinction ratio of each channel
+    pub fn fit(&mut self) -> bool {
+        if self.samples.len() < 3 {
+            return false;
+        }
+        let rows: Vec<[f64; 3]> = self
+            .samples
+            .iter()
+            .map(|(phi, _)| {
+                let phi = (2. * phi).to_radians();
+                [1., phi.cos(), phi.sin()]
+            })
+            .collect();
+        let design = Mat::from_slice_2d(&rows).unwrap();
+
+        let mut analysers = self.analysers;
+        for (n, analyser) in analysers.iter_mut().enumerate() {
+            let values: Vec<[f64; 1]> = self.samples.iter().map(|(_, i)| [i[n]]).collect();
+            let values = Mat::from_slice_2d(&values).unwrap();
+            let mut x = Mat::default();
+            if !solve(&design, &values, &mut x, DECOMP_SVD).unwrap() {
+                return false;
+            }
+            let [a, b, c] = [0, 1, 2].map(|i| *x.at::<f64>(i).unwrap());
+            let amplitude = b.hypot(c);
+            if a <= 0. || amplitude > a {
+                return false;
+            }
+            analyser.angle = (0.5 * c.atan2(b).to_degrees()).rem_euclid(180.);
+            analyser.extinction = (a - amplitude) / (a + amplitude);
+        }
+        self.analysers = analysers;
+        true
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui) {
+        ui.text("polarizer calibration:");
+        if !self.status.is_empty() {
+            ui.same_line();
+            ui.text(format!("no DoLP/AoLP: {}", self.status));
+        }
+        for (n, analyser) in self.analysers.iter_mut().enumerate() {
+            let mut values = [analyser.angle as f32, analyser.extinction as f32];
+            if ui
+                .input_float2(
+                    format!("{} angle, extinction", ["left", "right"][n]),
+                    &mut values,
+                )
+                .build()
+            {
+                analyser.angle = values[0] as f64;
+                analyser.extinction = values[1].clamp(0., 1.) as f64;
+            }
+        }
+        ui.input_float("reference angle", &mut self.reference_angle)
+            .build();
+        ui.input_float("reference step", &mut self.step).build();
+
+        if ui.button("add sample") {
+            self.sample_requested = true;
+        }
+        ui.same_line();
+        if ui.button("fit") && !self.fit() {
+            ui.open_popup("fit failed");
+        }
+        ui.same_line();
+        if ui.button("clear samples") {
+            self.samples.clear();
+        }
+        ui.popup("fit failed", || {
+            ui.text("need at least 3 samples spanning the reference rotation");
+        });
+        ui.text(format!("{} samples", self.samples.len()));
+        if self.inverse().is_none() {
+            ui.text("analysers are degenerate, DoLP/AoLP unavailable");
+        }
+    }
+}
+
+impl Persist for Polarimetry {
+    fn save(&self, storage: &mut FileStorage) -> Result<()> {
+        for (n, analyser) in self.analysers.iter().enumerate() {
+            let prefix = ["left", "right"][n];
+            storage.write_f64(&format!("{}_angle", prefix), analyser.angle)?;
+            storage.write_f64(&format!("{}_extinction", prefix), analyser.extinction)?;
+        }
+        Ok(())
+    }
+
+    fn load(&mut self, node: &FileNode) -> Result<()> {
+        for (n, analyser) in self.analysers.iter_mut().enumerate() {
+            let prefix = ["left", "right"][n];
+            profile::read_f64(node, &format!("{}_angle", prefix), &mut analyser.angle)?;
+            profile::read_f64(
+                node,
+                &format!("{}_extinction", prefix),
+                &mut analyser.extinction,
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// grayscale intensity as CV_32F
+fn intensity(mat: &Mat) -> Mat {
+    let (mut gray, mut float) = (Mat::default(), Mat::default());
+    imgproc::cvt_color_def(mat, &mut gray, imgproc::COLOR_BGR2GRAY).unwrap();
+    gray.convert_to_def(&mut float, CV_32F).unwrap();
+    float
+}
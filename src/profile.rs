@@ -0,0 +1,96 @@
+use crate::*;
+
+const PROFILE_FOLDER: &str = "profiles";
+
+/// settings that are saved to and loaded from a profile
+pub trait Persist {
+    fn save(&self, storage: &mut FileStorage) -> Result<()>;
+    /// keys missing from the node keep their current value
+    fn load(&mut self, node: &FileNode) -> Result<()>;
+}
+
+pub fn get_filepath(name: &str) -> String {
+    let mut filepath = path::PathBuf::from(PROFILE_FOLDER);
+    filepath.push(format!("{}.yml", name));
+    filepath.to_str().unwrap().to_string()
+}
+
+/// names of the saved profiles
+pub fn list() -> Vec<String> {
+    let Ok(dir) = fs::read_dir(PROFILE_FOLDER) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = dir
+        .filter_map(|item| {
+            let name = item.ok()?.file_name().into_string().ok()?;
+            Some(name.strip_suffix(".yml")?.to_string())
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+pub fn save(name: &str, item: &impl Persist) -> Result<()> {
+    fs::create_dir_all(PROFILE_FOLDER)
+        .map_err(|err| opencv::Error::new(StsError, format!("{}: {}", PROFILE_FOLDER, err)))?;
+    let mut storage = FileStorage::new(&get_filepath(name), FileStorage_WRITE, "")?;
+    item.save(&mut storage)?;
+    storage.release()
+}
+
+pub fn load(name: &str, item: &mut impl Persist) -> Result<()> {
+    let filepath = get_filepath(name);
+    if !path::Path::new(&filepath).exists() {
+        return Err(opencv::Error::new(
+            StsObjectNotFound,
+            format!("no profile at {}", filepath),
+        ));
+    }
+    let storage = FileStorage::new(&filepath, FileStorage_READ, "")?;
+    item.load(&storage.root_def()?)
+}
+
+pub fn save_struct(storage: &mut FileStorage, name: &str, item: &impl Persist) -> Result<()> {
+    storage.start_write_struct_def(name, FileNode_MAP)?;
+    item.save(storage)?;
+    storage.end_write_struct()
+}
+
+pub fn load_struct(node: &FileNode, name: &str, item: &mut impl Persist) -> Result<()> {
+    let node = node.get(name)?;
+    if node.empty()? {
+        return Ok(());
+    }
+    item.load(&node)
+}
+
+pub fn read_i32(node: &FileNode, name: &str, value: &mut i32) -> Result<()> {
+    let node = node.get(name)?;
+    if !node.empty()? {
+        *value = node.to_i32()?;
+    }
+    Ok(())
+}
+
+pub fn read_f64(node: &FileNode, name: &str, value: &mut f64) -> Result<()> {
+    let node = node.get(name)?;
+    if !node.empty()? {
+        *value = node.to_f64()?;
+    }
+    Ok(())
+}
+
+pub fn read_bool(node: &FileNode, name: &str, value: &mut bool) -> Result<()> {
+    let mut int = *value as i32;
+    read_i32(node, name, &mut int)?;
+    *value = int != 0;
+    Ok(())
+}
+
+pub fn read_string(node: &FileNode, name: &str, value: &mut String) -> Result<()> {
+    let node = node.get(name)?;
+    if !node.empty()? {
+        *value = node.to_string()?;
+    }
+    Ok(())
+}
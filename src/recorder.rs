@@ -0,0 +1,411 @@
+use crate::profile::{self, Persist};
+use crate::*;
+use mpsc::{Receiver, SyncSender, TrySendError};
+use rayon::prelude::*;
+use std::io::{BufWriter, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+/// streams that can be recorded, each to its own files in the format of the recorder
+pub const STREAMS: [&str; 12] = [
+    "left raw",
+    "right raw",
+    "left",
+    "right",
+    "subtracted",
+    "DoLP",
+    "AoLP",
+    "left overlay",
+    "right overlay",
+    "subtracted overlay",
+    "DoLP overlay",
+    "AoLP overlay",
+];
+/// first stream of each capture stage: raw cameras, feeds before and after the overlays
+pub const RAW: usize = 0;
+pub const CLEAN: usize = 2;
+pub const OVERLAY: usize = 7;
+
+/// frames waiting for the writer thread before new ones are dropped
+const QUEUE: usize = 64;
+/// file rate until the capture rate is measured
+const DEFAULT_FPS: f64 = 15.;
+
+/// every selected stream of one frame
+struct Packet {
+    frame: u64,
+    timestamp: f64,
+    size: Size,
+    mats: Vec<(usize, Mat)>,
+}
+
+/// what the writer thread reports back
+#[derive(Default)]
+struct Progress {
+    written: AtomicU64,
+    error: Mutex<Option<String>>,
+}
+
+/// one recording: the streams of the current frame, and the thread writing finished frames
+struct Session {
+    /// file name prefix shared by every file of the recording
+    prefix: String,
+    /// size of the left camera, for streams that start out empty
+    size: Option<Size>,
+    mats: Vec<(usize, Mat)>,
+    packets: Option<SyncSender<Packet>>,
+    thread: Option<thread::JoinHandle<()>>,
+    progress: Arc<Progress>,
+    /// frames handed to the thread
+    queued: u64,
+    /// frames left out of every stream because the queue was full
+    dropped: u64,
+    /// started by a trigger, which may also stop it
+    triggered: bool,
+}
+
+impl Session {
+    /// waits for the queue to drain
+    fn finish(&mut self) {
+        self.packets = None;
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// records the selected feeds into separate, frame-synchronised files on a writer thread
+pub struct Recorder {
+    /// in the order of `STREAMS`
+    pub streams: [bool; STREAMS.len()],
+    /// index into `sink::FORMATS`
+    pub format: usize,
+    /// frame rate of the files, 0 for the measured capture rate
+    pub fps: f64,
+    /// frames per second of the main loop, averaged
+    pub rate: f64,
+    pub status: String,
+    /// a triggered start failed, not retried until the trigger lets go
+    trigger_failed: bool,
+    last_frame: Option<f64>,
+    session: Option<Session>,
+}
+
+impl Default for Recorder {
+    fn default() -> Self {
+        let mut streams = [false; STREAMS.len()];
+        streams[OVERLAY + 2] = true;
+        Self {
+            streams,
+            format: 0,
+            fps: 0.,
+            rate: 0.,
+            status: String::new(),
+            trigger_failed: false,
+            last_frame: None,
+            session: None,
+        }
+    }
+}
+
+/// 8-bit BGR, black at `size` for an empty feed
+fn prepared(mat: &Mat, size: Size) -> Mat {
+    if mat.empty() {
+        return Mat::new_size_with_default(size, CV_8UC3, Scalar::all(0.)).unwrap();
+    }
+    if mat.channels() == 1 {
+        let mut bgr = Mat::default();
+        imgproc::cvt_color_def(mat, &mut bgr, imgproc::COLOR_GRAY2BGR).unwrap();
+        return bgr;
+    }
+    mat.clone()
+}
+
+/// where one stream is written, at the size of its first frame
+#[derive(Default)]
+struct Stream {
+    sink: Option<Box<dyn sink::Sink>>,
+    size: Option<Size>,
+    failed: bool,
+}
+
+impl Stream {
+    fn write(&mut self, n: usize, mat: &Mat, args: (usize, &str, f64, Size)) -> Result<(), String> {
+        let (format, prefix, fps, camera) = args;
+        let size = *self.size.get_or_insert(match mat.empty() {
+            true => camera,
+            false => mat.size().unwrap(),
+        });
+        let frame = prepared(mat, size);
+        if frame.size().unwrap() != size {
+            return Err(format!("{} changed size, stopped", STREAMS[n]));
+        }
+        if self.sink.is_none() {
+            let name = STREAMS[n].replace(' ', "_");
+            self.sink = Some(sink::open(format, prefix, &name, fps, size)?);
+        }
+        let sink = self.sink.as_mut().unwrap();
+        sink.write(&frame)
+            .map_err(|err| format!("{} stopped: {}", STREAMS[n], err))
+    }
+}
+
+/// body of the writer thread: a sink per stream, opened at its first frame and written in
+/// parallel with the others, and a row in the index for every frame written
+fn write(
+    format: usize,
+    prefix: String,
+    fps: f64,
+    mut index: BufWriter<fs::File>,
+    packets: Receiver<Packet>,
+    progress: Arc<Progress>,
+) {
+    let fail = |error: String| *progress.error.lock().unwrap() = Some(error);
+    let mut streams: Vec<Stream> = STREAMS.iter().map(|_| Stream::default()).collect();
+    for packet in packets {
+        let mut frames: Vec<Option<Mat>> = STREAMS.iter().map(|_| None).collect();
+        for (stream, mat) in packet.mats {
+            frames[stream] = Some(mat);
+        }
+        let args = (format, prefix.as_str(), fps, packet.size);
+        let errors: Vec<String> = streams
+            .par_iter_mut()
+            .zip(frames)
+            .enumerate()
+            .filter_map(|(n, (stream, mat))| {
+                let mat = mat.filter(|_| !stream.failed)?;
+                let error = stream.write(n, &mat, args).err()?;
+                stream.failed = true;
+                Some(error)
+            })
+            .collect();
+        for error in errors {
+            fail(error);
+        }
+
+        let written = progress.written.load(Ordering::Relaxed);
+        let row = writeln!(
+            index,
+            "{},{},{:.6}",
+            written, packet.frame, packet.timestamp
+        );
+        if let Err(err) = row {
+            fail(format!("recording index stopped: {}", err));
+            return;
+        }
+        progress.written.fetch_add(1, Ordering::Relaxed);
+    }
+    let _ = index.flush();
+}
+
+impl Recorder {
+    pub fn recording(&self) -> bool {
+        self.session.is_some()
+    }
+
+    pub fn start(&mut self) -> std::io::Result<()> {
+        let path = utils::get_save_filepath("recording.csv");
+        let mut index = BufWriter::new(fs::File::create(&path)?);
+        writeln!(index, "index,frame,timestamp")?;
+        let prefix: String = path.trim_end_matches("recording.csv").into();
+        let fps = match (self.fps, self.rate) {
+            (fps, _) if fps > 0. => fps,
+            (_, rate) if rate > 0. => rate,
+            _ => DEFAULT_FPS,
+        };
+        let (sender, packets) = mpsc::sync_channel(QUEUE);
+        let progress = Arc::new(Progress::default());
+        let thread = {
+            let (format, prefix, progress) = (self.format, prefix.clone(), progress.clone());
+            thread::spawn(move || write(format, prefix, fps, index, packets, progress))
+        };
+        self.session = Some(Session {
+            prefix,
+            size: None,
+            mats: Vec::new(),
+            packets: Some(sender),
+            thread: Some(thread),
+            progress,
+            queued: 0,
+            dropped: 0,
+            triggered: false,
+        });
+        self.status = format!("recording at {:.1} fps", fps);
+        Ok(())
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(mut session) = self.session.take() {
+            session.finish();
+            let written = session.progress.written.load(Ordering::Relaxed);
+            self.status = format!(
+                "{} frames to {}*, {} dropped",
+                written, session.prefix, session.dropped
+            );
+            if let Some(error) = session.progress.error.lock().unwrap().take() {
+                self.status = error;
+            }
+        }
+    }
+
+    /// starts or stops the recording as a trigger wants it, leaving the ones started by hand
+    pub fn follow(&mut self, wanted: bool) {
+        let triggered = self.session.as_ref().map(|s| s.triggered);
+        match (triggered, wanted) {
+            (None, true) if !self.trigger_failed => match self.start() {
+                Ok(()) => self.session.as_mut().unwrap().triggered = true,
+                Err(err) => {
+                    self.status = format!("could not start recording: {}", err);
+                    self.trigger_failed = true;
+                }
+            },
+            (Some(true), false) => self.stop(),
+            (None, false) => self.trigger_failed = false,
+            _ => {}
+        }
+    }
+
+    /// keeps the selected streams from `first` on for the current frame
+    fn capture(&mut self, first: usize, mats: &[&Mat]) {
+        let Some(ref mut session) = self.session else {
+            return;
+        };
+        for (n, mat) in mats.iter().enumerate() {
+            if self.streams[first + n] {
+                session.mats.push((first + n, (*mat).clone()));
+            }
+        }
+    }
+
+    /// the camera frames as read, before the shift and null subtraction
+    pub fn raw(&mut self, cameras: [&Mat; 2]) {
+        if let Some(ref mut session) = self.session {
+            if session.size.is_none() && !cameras[0].empty() {
+                session.size = Some(cameras[0].size().unwrap());
+            }
+        }
+        self.capture(RAW, &cameras);
+    }
+
+    /// left, right, subtracted, DoLP and AoLP before anything is drawn on them
+    pub fn clean(&mut self, feeds: [&Mat; 5]) {
+        self.capture(CLEAN, &feeds);
+    }
+
+    /// the same feeds with the overlays, ending the frame: it goes to the writer thread,
+    /// or is dropped from every stream if the queue is full
+    pub fn overlays(&mut self, feeds: [&Mat; 5], frame: u64) {
+        let timestamp = utils::timestamp();
+        if let Some(last) = self.last_frame.replace(timestamp) {
+            let interval = timestamp - last;
+            if interval > 0. {
+                self.rate = match self.rate {
+                    rate if rate > 0. => 0.9 * rate + 0.1 / interval,
+                    _ => 1. / interval,
+                };
+            }
+        }
+
+        self.capture(OVERLAY, &feeds);
+        let Some(ref mut session) = self.session else {
+            return;
+        };
+        let mats = std::mem::take(&mut session.mats);
+        let Some(size) = session.size else {
+            return;
+        };
+        let packet = Packet {
+            frame,
+            timestamp,
+            size,
+            mats,
+        };
+        let sent = match session.packets {
+            Some(ref packets) => packets.try_send(packet),
+            None => return,
+        };
+        match sent {
+            Ok(()) => session.queued += 1,
+            Err(TrySendError::Full(_)) => session.dropped += 1,
+            Err(TrySendError::Disconnected(_)) => self.stop(),
+        }
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui) {
+        ui.text("recording:");
+        ui.same_line();
+        match self.session {
+            Some(ref session) => {
+                let written = session.progress.written.load(Ordering::Relaxed);
+                let (queue, dropped) = (session.queued - written, session.dropped);
+                if let Some(ref error) = *session.progress.error.lock().unwrap() {
+                    self.status = error.clone();
+                }
+                if ui.button("stop##recording") {
+                    self.stop();
+                }
+                ui.same_line();
+                ui.text(format!(
+                    "{} frames, {} queued, {} dropped",
+                    written, queue, dropped
+                ));
+            }
+            None => {
+                if ui.button("start##recording") {
+                    if let Err(err) = self.start() {
+                        self.status = format!("could not start recording: {}", err);
+                    }
+                }
+                ui.same_line();
+                ui.set_next_item_width(120.);
+                ui.input_scalar("fps (0 measured)##recording", &mut self.fps)
+                    .build();
+            }
+        }
+        ui.text(format!("capture rate {:.1} fps", self.rate));
+        if !self.status.is_empty() {
+            ui.same_line();
+            ui.text(&self.status);
+        }
+        let recording = self.recording();
+        ui.disabled(recording, || {
+            ui.combo_simple_string("format##recording", &mut self.format, &sink::FORMATS);
+        });
+        for (n, name) in STREAMS.iter().enumerate() {
+            if n % 4 != 0 {
+                ui.same_line();
+            }
+            // streams cannot join or leave a recording without breaking the alignment
+            ui.disabled(recording, || {
+                ui.checkbox(format!("{}##stream", name), &mut self.streams[n]);
+            });
+        }
+    }
+}
+
+impl Drop for Recorder {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+impl Persist for Recorder {
+    fn save(&self, storage: &mut FileStorage) -> Result<()> {
+        let streams = (0..STREAMS.len()).filter(|n| self.streams[*n]);
+        storage.write_i32("streams", streams.map(|n| 1 << n).sum())?;
+        storage.write_i32("format", self.format as i32)?;
+        storage.write_f64("fps", self.fps)
+    }
+
+    fn load(&mut self, node: &FileNode) -> Result<()> {
+        let mut streams = -1;
+        profile::read_i32(node, "streams", &mut streams)?;
+        if streams >= 0 {
+            self.streams = std::array::from_fn(|n| streams & 1 << n != 0);
+        }
+        let mut format = self.format as i32;
+        profile::read_i32(node, "format", &mut format)?;
+        self.format = (format.max(0) as usize).min(sink::FORMATS.len() - 1);
+        profile::read_f64(node, "fps", &mut self.fps)
+    }
+}
@@ -0,0 +1,127 @@
+use crate::*;
+
+pub const VIEWS: [&str; 4] = ["checkerboard", "anaglyph", "flicker", "edges"];
+
+/// alignment views of the two registered feeds, used while tuning `cam_shift`
+pub struct Alignment {
+    pub view: usize,
+    pub tile: i32,
+    pub flicker_period: i32,
+    pub score: Option<f64>,
+    pub feed: image::Image,
+    frame: i32,
+}
+
+impl Default for Alignment {
+    fn default() -> Self {
+        Self {
+            view: 0,
+            tile: 40,
+            flicker_period: 10,
+            score: None,
+            feed: Default::default(),
+            frame: 0,
+        }
+    }
+}
+
+impl Alignment {
+    pub fn update(&mut self, mat1: &Mat, mat2: &Mat) {
+        if mat1.empty() || mat1.size().unwrap() != mat2.size().unwrap() {
+            self.score = None;
+            return;
+        }
+        self.score = Some(ncc(mat1, mat2));
+        self.frame = (self.frame + 1) % (2 * self.flicker_period.max(1));
+
+        let out = &mut self.feed.mat;
+        match self.view {
+            0 => checkerboard(mat1, mat2, self.tile.max(1), out),
+            1 => anaglyph(mat1, mat2, out),
+            2 => [mat1, mat2][(self.frame >= self.flicker_period) as usize]
+                .copy_to(out)
+                .unwrap(),
+            _ => edges(mat1, mat2, out),
+        }
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui) {
+        ui.combo_simple_string("alignment view", &mut self.view, &VIEWS);
+        match self.view {
+            0 => {
+                ui.slider("tile size", 4, 200, &mut self.tile);
+            }
+            2 => {
+                ui.slider("flicker period", 1, 60, &mut self.flicker_period);
+            }
+            _ => {}
+        }
+        match self.score {
+            Some(score) => ui.text(format!("NCC: {:.4}", score)),
+            None => ui.text("NCC: -"),
+        }
+    }
+}
+
+fn gray(mat: &Mat) -> Mat {
+    let mut gray = Mat::default();
+    imgproc::cvt_color_def(mat, &mut gray, imgproc::COLOR_BGR2GRAY).unwrap();
+    gray
+}
+
+/// normalised cross-correlation of the two feeds, in [-1, 1]
+pub fn ncc(mat1: &Mat, mat2: &Mat) -> f64 {
+    let mut result = Mat::default();
+    imgproc::match_template_def(
+        &gray(mat1),
+        &gray(mat2),
+        &mut result,
+        imgproc::TM_CCOEFF_NORMED,
+    )
+    .unwrap();
+    *result.at_2d::<f32>(0, 0).unwrap() as f64
+}
+
+fn checkerboard(mat1: &Mat, mat2: &Mat, tile: i32, out: &mut Mat) {
+    let size = mat1.size().unwrap();
+    let mut mask = Mat::new_size_with_default(size, CV_8UC1, Scalar::all(0.)).unwrap();
+    for x in (0..size.width).step_by(tile as usize) {
+        for y in (0..size.height).step_by(tile as usize) {
+            if (x / tile + y / tile) % 2 == 1 {
+                let rect = Rect::new(x, y, tile, tile);
+                imgproc::rectangle(
+                    &mut mask,
+                    rect,
+                    Scalar::all(255.),
+                    imgproc::FILLED,
+                    imgproc::LINE_8,
+                    0,
+                )
+                .unwrap();
+            }
+        }
+    }
+    mat1.copy_to(out).unwrap();
+    mat2.copy_to_masked(out, &mask).unwrap();
+}
+
+/// left feed in red, right feed in cyan
+fn anaglyph(mat1: &Mat, mat2: &Mat, out: &mut Mat) {
+    let (g1, g2) = (gray(mat1), gray(mat2));
+    let channels = Vector::<Mat>::from_iter([g2.clone(), g2, g1]);
+    merge(&channels, out).unwrap();
+}
+
+/// left feed edges in red, right feed edges in cyan, over the dimmed left feed
+fn edges(mat1: &Mat, mat2: &Mat, out: &mut Mat) {
+    let (mut e1, mut e2) = (Mat::default(), Mat::default());
+    imgproc::canny_def(&gray(mat1), &mut e1, 50., 150.).unwrap();
+    imgproc::canny_def(&gray(mat2), &mut e2, 50., 150.).unwrap();
+
+    let mut base = Mat::default();
+    imgproc::cvt_color_def(&gray(mat1), &mut base, imgproc::COLOR_GRAY2BGR).unwrap();
+    base.convert_to(out, -1, 0.5, 0.).unwrap();
+    out.set_to(&Scalar::from([0., 0., 255., 0.]), &e1).unwrap();
+    out.set_to(&Scalar::from([255., 255., 0., 0.]), &e2)
+        .unwrap();
+}
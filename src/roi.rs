@@ -0,0 +1,605 @@
+use crate::profile::{self, Persist};
+use crate::*;
+use imgui::{Key, MouseButton};
+
+pub const TOOLS: [&str; 5] = ["select", "rectangle", "ellipse", "polygon", "line profile"];
+/// the line profile tool is handled by `line_profile::LineProfile`
+pub const LINE_TOOL: usize = 4;
+
+/// screen pixels around the bottom right corner that grab the resize handle
+const HANDLE: f32 = 8.;
+
+#[derive(Clone, Debug)]
+pub enum Shape {
+    Rectangle(Rect),
+    /// ellipse inscribed in the rect
+    Ellipse(Rect),
+    Polygon(Vec<Point>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Roi {
+    /// unique among the rois of a run, kept through renames
+    pub id: usize,
+    pub name: String,
+    pub shape: Shape,
+    /// feature the roi follows from frame to frame
+    pub anchor: Option<anchor::Anchor>,
+}
+
+impl Roi {
+    pub fn bounds(&self) -> Rect {
+        match &self.shape {
+            Shape::Rectangle(rect) | Shape::Ellipse(rect) => *rect,
+            Shape::Polygon(points) => {
+                let points = Vector::<Point>::from_iter(points.iter().copied());
+                imgproc::bounding_rect(&points).unwrap()
+            }
+        }
+    }
+
+    /// bounds clipped to the frame, and the shape mask over them
+    pub fn mask(&self, size: Size) -> (Rect, Mat) {
+        let rect = self.bounds() & Rect::new(0, 0, size.width, size.height);
+        let rect = Rect::new(rect.x, rect.y, rect.width.max(0), rect.height.max(0));
+        let mut mask = Mat::new_size_with_default(rect.size(), CV_8UC1, Scalar::all(0.)).unwrap();
+        let offset = Point::new(-rect.x, -rect.y);
+        let white = Scalar::all(255.);
+        match &self.shape {
+            Shape::Rectangle(_) => {
+                mask.set_to_def(&white).unwrap();
+            }
+            Shape::Ellipse(bounds) => {
+                let (center, axes) = ellipse_axes(*bounds);
+                let center = center + offset;
+                imgproc::ellipse(
+                    &mut mask,
+                    center,
+                    axes,
+                    0.,
+                    0.,
+                    360.,
+                    white,
+                    imgproc::FILLED,
+                    imgproc::LINE_8,
+                    0,
+                )
+                .unwrap();
+            }
+            Shape::Polygon(points) => {
+                let points: Vector<Point> = points.iter().map(|p| *p + offset).collect();
+                let polygons = Vector::<Vector<Point>>::from_iter([points]);
+                imgproc::fill_poly_def(&mut mask, &polygons, white).unwrap();
+            }
+        }
+        (rect, mask)
+    }
+
+    pub fn contains(&self, point: Point) -> bool {
+        match &self.shape {
+            Shape::Rectangle(rect) => rect.contains(point),
+            Shape::Ellipse(rect) => {
+                let (center, axes) = ellipse_axes(*rect);
+                let dx = (point.x - center.x) as f64 / axes.width.max(1) as f64;
+                let dy = (point.y - center.y) as f64 / axes.height.max(1) as f64;
+                dx * dx + dy * dy <= 1.
+            }
+            Shape::Polygon(points) => {
+                let points = Vector::<Point>::from_iter(points.iter().copied());
+                let point = Point2f::new(point.x as f32, point.y as f32);
+                imgproc::point_polygon_test(&points, point, false).unwrap() >= 0.
+            }
+        }
+    }
+
+    /// the shape as saved in profiles: 0 rectangle, 1 ellipse or 2 polygon, and its points,
+    /// the corners for the rects
+    fn points(&self) -> (usize, Vec<Point>) {
+        match &self.shape {
+            Shape::Rectangle(rect) => (0, vec![rect.tl(), rect.br()]),
+            Shape::Ellipse(rect) => (1, vec![rect.tl(), rect.br()]),
+            Shape::Polygon(points) => (2, points.clone()),
+        }
+    }
+
+    fn from_points(name: String, shape: usize, points: &[Point]) -> Option<Self> {
+        let shape = match (shape, points) {
+            (0, [tl, br]) => Shape::Rectangle(Rect::from_points(*tl, *br)),
+            (1, [tl, br]) => Shape::Ellipse(Rect::from_points(*tl, *br)),
+            (2, points) if points.len() >= 3 => Shape::Polygon(points.to_vec()),
+            _ => return None,
+        };
+        Some(Self {
+            id: 0,
+            name,
+            shape,
+            anchor: None,
+        })
+    }
+
+    pub fn translate(&mut self, delta: Point) {
+        match &mut self.shape {
+            Shape::Rectangle(rect) | Shape::Ellipse(rect) => *rect += delta,
+            Shape::Polygon(points) => points.iter_mut().for_each(|p| *p += delta),
+        }
+    }
+
+    /// scales the shape to fit the new bounds
+    pub fn resize(&mut self, bounds: Rect) {
+        let old = self.bounds();
+        match &mut self.shape {
+            Shape::Rectangle(rect) | Shape::Ellipse(rect) => *rect = bounds,
+            Shape::Polygon(points) => {
+                let scale = |v: i32, from: i32, to: i32, old: i32, new: i32| {
+                    to + ((v - from) as f64 * new as f64 / old.max(1) as f64).round() as i32
+                };
+                for p in points {
+                    p.x = scale(p.x, old.x, bounds.x, old.width, bounds.width);
+                    p.y = scale(p.y, old.y, bounds.y, old.height, bounds.height);
+                }
+            }
+        }
+    }
+
+    pub fn draw(&self, mat: &mut Mat, color: Scalar) {
+        match &self.shape {
+            Shape::Rectangle(rect) => imgproc::rectangle_def(mat, *rect, color).unwrap(),
+            Shape::Ellipse(rect) => {
+                let (center, axes) = ellipse_axes(*rect);
+                imgproc::ellipse_def(mat, center, axes, 0., 0., 360., color).unwrap();
+            }
+            Shape::Polygon(points) => {
+                let points = Vector::<Point>::from_iter(points.iter().copied());
+                let polygons = Vector::<Vector<Point>>::from_iter([points]);
+                imgproc::polylines_def(mat, &polygons, true, color).unwrap();
+            }
+        }
+        let bounds = self.bounds();
+        let org = Point::new(bounds.x, bounds.y - 4);
+        let font = imgproc::FONT_HERSHEY_SIMPLEX;
+        imgproc::put_text_def(mat, &self.name, org, font, 0.4, color).unwrap();
+    }
+}
+
+fn ellipse_axes(rect: Rect) -> (Point, Size) {
+    let center = Point::new(rect.x + rect.width / 2, rect.y + rect.height / 2);
+    (center, Size::new(rect.width / 2, rect.height / 2))
+}
+
+enum Drag {
+    Create(Point),
+    Move(Point),
+    Resize,
+}
+
+/// user-defined regions of interest, edited with the mouse over any feed window
+#[derive(Default)]
+pub struct Rois {
+    pub list: Vec<Roi>,
+    pub selected: Option<usize>,
+    pub tool: usize,
+    pub readings: Vec<stats::RoiStats>,
+    /// columns of `stats::COLUMNS` and `stats::POLAR_COLUMNS` hidden from the tables
+    pub hidden: [bool; stats::COLUMNS.len()],
+    pub hidden_polar: [bool; stats::POLAR_COLUMNS.len()],
+    pub tracking: anchor::Tracking,
+    /// index into `anchor::KINDS` used by the attach buttons
+    pub anchor_kind: usize,
+    anchor_error: String,
+    /// feed window index and state of the current mouse drag
+    drag: Option<(usize, Drag)>,
+    preview: Option<Roi>,
+    polygon: Vec<Point>,
+    count: usize,
+    /// ids handed out so far
+    ids: usize,
+    seeded: bool,
+}
+
+impl Rois {
+    fn next_id(&mut self) -> usize {
+        self.ids += 1;
+        self.ids
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Roi> {
+        self.list.iter().find(|roi| roi.id == id)
+    }
+
+    /// adds the default left and center windows once the frame size is known
+    pub fn seed(&mut self, size: Size, window: i32, shift: [i32; 2]) {
+        if self.seeded {
+            return;
+        }
+        self.seeded = true;
+        let [x, y] = [(size.width - window) / 2, (size.height - window) / 2];
+        let left = Rect::new(x + shift[0], y + shift[1], window, window);
+        let center = Rect::new(x, y, window, window);
+        for (name, rect) in [("left", left), ("center", center)] {
+            let id = self.next_id();
+            self.list.push(Roi {
+                id,
+                name: name.into(),
+                shape: Shape::Rectangle(rect),
+                anchor: None,
+            });
+        }
+    }
+
+    /// moves the anchored rois with their features in the new frame
+    pub fn track(&mut self, frame: &Mat) {
+        if frame.empty() {
+            return;
+        }
+        let needs_markers = self.list.iter().any(|roi| {
+            matches!(
+                roi.anchor,
+                Some(anchor::Anchor {
+                    tracker: anchor::Tracker::Marker(_),
+                    ..
+                })
+            )
+        });
+        self.tracking.next_frame(frame, needs_markers);
+        for roi in &mut self.list {
+            if let Some(mut anchor) = roi.anchor.take() {
+                let delta = anchor.update(&self.tracking);
+                roi.translate(delta);
+                anchor.reseed(roi, &self.tracking);
+                roi.anchor = Some(anchor);
+            }
+        }
+    }
+
+    /// statistics of every channel of the left, right and subtracted feeds, and of DoLP/AoLP
+    pub fn measure(&mut self, feeds: &[&Mat; 3], polarimetry: &polarimetry::Polarimetry) {
+        let size = feeds[0].size().unwrap();
+        let has_polar = !polarimetry.dolp.empty() && polarimetry.dolp.size().unwrap() == size;
+        self.readings = self
+            .list
+            .iter()
+            .map(|roi| {
+                let (rect, mask) = roi.mask(size);
+                if rect.empty() {
+                    return stats::RoiStats::default();
+                }
+                stats::RoiStats {
+                    feeds: feeds.map(|feed| match feed.size().unwrap() == size {
+                        true => stats::channels(&feed.roi(rect).unwrap(), &mask),
+                        false => Default::default(),
+                    }),
+                    polar: has_polar.then(|| {
+                        let dolp = polarimetry.dolp.roi(rect).unwrap();
+                        let aolp = polarimetry.aolp.roi(rect).unwrap();
+                        stats::polar(&dolp, &aolp, &mask)
+                    }),
+                }
+            })
+            .collect();
+    }
+
+    pub fn draw(&self, mat: &mut Mat) {
+        let black: Scalar = [0., 0., 0., 255.].into();
+        let yellow: Scalar = [0., 255., 255., 255.].into();
+        let red: Scalar = [0., 0., 255., 255.].into();
+        self.tracking.draw(mat);
+        for (n, roi) in self.list.iter().enumerate() {
+            let lost = roi.anchor.as_ref().is_some_and(|a| a.lost);
+            let color = match (lost, self.selected == Some(n)) {
+                (true, _) => red,
+                (_, true) => yellow,
+                _ => black,
+            };
+            roi.draw(mat, color);
+        }
+        if let Some(ref preview) = self.preview {
+            preview.draw(mat, yellow);
+        }
+        if !self.polygon.is_empty() {
+            let points = Vector::<Point>::from_iter(self.polygon.iter().copied());
+            let polygons = Vector::<Vector<Point>>::from_iter([points]);
+            imgproc::polylines_def(mat, &polygons, false, yellow).unwrap();
+        }
+    }
+
+    /// handles mouse editing over a feed image
+    pub fn interact(&mut self, ui: &imgui::Ui, canvas: &image::Canvas) {
+        let point = canvas.pixel();
+        if canvas.hovered && ui.is_mouse_clicked(MouseButton::Left) {
+            self.press(ui, canvas);
+        }
+        if canvas.hovered && ui.is_mouse_clicked(MouseButton::Right) {
+            self.finish_polygon();
+        }
+        if canvas.hovered && ui.is_key_pressed(Key::Delete) {
+            if let Some(n) = self.selected.take() {
+                self.list.remove(n);
+            }
+        }
+
+        let Some((drag_window, ref mut drag)) = self.drag else {
+            return;
+        };
+        if drag_window != canvas.window {
+            return;
+        }
+        match drag {
+            Drag::Create(start) => {
+                let rect = Rect::from_points(*start, point);
+                let shape = match self.tool {
+                    2 => Shape::Ellipse(rect),
+                    _ => Shape::Rectangle(rect),
+                };
+                self.preview = Some(Roi {
+                    id: 0,
+                    name: String::new(),
+                    shape,
+                    anchor: None,
+                });
+            }
+            Drag::Move(last) => {
+                if let Some(n) = self.selected {
+                    self.list[n].translate(point - *last);
+                }
+                *last = point;
+            }
+            Drag::Resize => {
+                if let Some(n) = self.selected {
+                    let tl = self.list[n].bounds().tl();
+                    let br = Point::new(point.x.max(tl.x + 2), point.y.max(tl.y + 2));
+                    self.list[n].resize(Rect::from_points(tl, br));
+                }
+            }
+        }
+
+        if ui.is_mouse_released(MouseButton::Left) {
+            if let Some(preview) = self.preview.take() {
+                if preview.bounds().width > 2 && preview.bounds().height > 2 {
+                    self.add(preview.shape);
+                }
+            }
+            self.drag = None;
+        }
+    }
+
+    fn press(&mut self, ui: &imgui::Ui, canvas: &image::Canvas) {
+        let (window, point) = (canvas.window, canvas.pixel());
+        match self.tool {
+            0 => {
+                let handle = self.selected.filter(|n| {
+                    let br = self.list[*n].bounds().br();
+                    let br = canvas.to_screen(Point2f::new(br.x as _, br.y as _));
+                    let mouse = ui.io().mouse_pos;
+                    (mouse[0] - br[0]).abs() < HANDLE && (mouse[1] - br[1]).abs() < HANDLE
+                });
+                if handle.is_some() {
+                    self.drag = Some((window, Drag::Resize));
+                    return;
+                }
+                self.selected = self.list.iter().rposition(|roi| roi.contains(point));
+                if self.selected.is_some() {
+                    self.drag = Some((window, Drag::Move(point)));
+                }
+            }
+            1 | 2 => self.drag = Some((window, Drag::Create(point))),
+            3 => {
+                if ui.is_mouse_double_clicked(MouseButton::Left) {
+                    self.finish_polygon();
+                } else {
+                    self.polygon.push(point);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish_polygon(&mut self) {
+        let points = std::mem::take(&mut self.polygon);
+        if points.len() >= 3 {
+            self.add(Shape::Polygon(points));
+        }
+    }
+
+    fn add(&mut self, shape: Shape) {
+        self.count += 1;
+        let id = self.next_id();
+        self.list.push(Roi {
+            id,
+            name: format!("roi {}", self.count),
+            shape,
+            anchor: None,
+        });
+        self.selected = Some(self.list.len() - 1);
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui) {
+        ui.combo_simple_string("roi tool", &mut self.tool, &TOOLS);
+        if self.tool == 3 {
+            ui.text("click to add vertices, double or right click to close");
+        }
+        if self.tool == LINE_TOOL {
+            ui.text("drag over any feed to draw the profile line");
+        }
+        ui.set_next_item_width(150.);
+        ui.combo_simple_string("anchor", &mut self.anchor_kind, &anchor::KINDS);
+        ui.same_line();
+        ui.checkbox("detect markers", &mut self.tracking.detect_markers);
+        if self.tracking.detect_markers {
+            ui.same_line();
+            ui.text(format!("{} in view", self.tracking.markers.len()));
+        }
+        let mut remove = None;
+        for (n, roi) in self.list.iter_mut().enumerate() {
+            let _id = ui.push_id_usize(n);
+            if ui.radio_button_bool("##select", self.selected == Some(n)) {
+                self.selected = Some(n);
+            }
+            ui.same_line();
+            ui.set_next_item_width(150.);
+            ui.input_text("##name", &mut roi.name).build();
+            ui.same_line();
+            if ui.button("delete") {
+                remove = Some(n);
+            }
+            ui.same_line();
+            match roi.anchor {
+                Some(ref anchor) => {
+                    if ui.button("detach") {
+                        roi.anchor = None;
+                    } else if anchor.lost {
+                        ui.same_line();
+                        ui.text_colored([1., 0.3, 0.3, 1.], "tracking lost");
+                    }
+                }
+                None => {
+                    if ui.button("attach") {
+                        roi.anchor = anchor::Anchor::attach(self.anchor_kind, roi, &self.tracking);
+                        self.anchor_error = match roi.anchor {
+                            Some(_) => String::new(),
+                            None => format!(
+                                "no {} to attach {} to",
+                                anchor::KINDS[self.anchor_kind],
+                                roi.name
+                            ),
+                        };
+                    }
+                }
+            }
+        }
+        if let Some(n) = remove {
+            self.list.remove(n);
+            self.selected = None;
+        }
+        if !self.anchor_error.is_empty() {
+            ui.text(&self.anchor_error);
+        }
+    }
+
+    /// tables of the statistics, one row per roi, feed and channel
+    pub fn table(&mut self, ui: &imgui::Ui) {
+        if ui.button("columns") {
+            ui.open_popup("roi columns");
+        }
+        ui.popup("roi columns", || {
+            for (n, name) in stats::COLUMNS.iter().enumerate() {
+                let mut visible = !self.hidden[n];
+                if ui.checkbox(name, &mut visible) {
+                    self.hidden[n] = !visible;
+                }
+            }
+            ui.separator();
+            for (n, name) in stats::POLAR_COLUMNS.iter().enumerate() {
+                let mut visible = !self.hidden_polar[n];
+                if ui.checkbox(name, &mut visible) {
+                    self.hidden_polar[n] = !visible;
+                }
+            }
+        });
+
+        let columns: Vec<usize> = (0..stats::COLUMNS.len())
+            .filter(|n| !self.hidden[*n])
+            .collect();
+        if let Some(_) = ui.begin_table("channel readings", 3 + columns.len()) {
+            for header in ["roi", "feed", "channel"] {
+                ui.table_setup_column(header);
+            }
+            for n in &columns {
+                ui.table_setup_column(stats::COLUMNS[*n]);
+            }
+            ui.table_headers_row();
+            for (roi, readings) in self.list.iter().zip(&self.readings) {
+                for (n, bgr) in readings.feeds.iter().enumerate() {
+                    for i in 0..3 {
+                        ui.table_next_column();
+                        if n == 0 && i == 0 {
+                            ui.text(&roi.name);
+                        }
+                        ui.table_next_column();
+                        if i == 0 {
+                            ui.text(format!("camera {}", n + 1));
+                        }
+                        ui.table_next_column();
+                        ui.text(["red", "green", "blue"][i]);
+                        let values = bgr[2 - i].values();
+                        for c in &columns {
+                            ui.table_next_column();
+                            ui.text(format!("{:.2}", values[*c]));
+                        }
+                    }
+                }
+            }
+        }
+
+        let columns: Vec<usize> = (0..stats::POLAR_COLUMNS.len())
+            .filter(|n| !self.hidden_polar[*n])
+            .collect();
+        if let Some(_) = ui.begin_table("polarimetric readings", 1 + columns.len()) {
+            ui.table_setup_column("roi");
+            for n in &columns {
+                ui.table_setup_column(stats::POLAR_COLUMNS[*n]);
+            }
+            ui.table_headers_row();
+            for (roi, readings) in self.list.iter().zip(&self.readings) {
+                ui.table_next_column();
+                ui.text(&roi.name);
+                let values = readings.polar.map(|p| p.values());
+                for c in &columns {
+                    ui.table_next_column();
+                    match values {
+                        Some(values) => ui.text(format!("{:.3}", values[*c])),
+                        None => ui.text("-"),
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl Persist for Rois {
+    /// the shapes and names of the rois as `roi_<n>`, without their anchors
+    fn save(&self, storage: &mut FileStorage) -> Result<()> {
+        storage.write_i32("count", self.list.len() as i32)?;
+        for (n, roi) in self.list.iter().enumerate() {
+            let (shape, points) = roi.points();
+            storage.start_write_struct_def(&format!("roi_{}", n), FileNode_MAP)?;
+            storage.write_str("name", &roi.name)?;
+            storage.write_i32("shape", shape as i32)?;
+            storage.write_mat("points", &Mat::from_exact_iter(points.into_iter())?)?;
+            storage.end_write_struct()?;
+        }
+        Ok(())
+    }
+
+    /// replaces the rois, and keeps the default ones from being seeded over them
+    fn load(&mut self, node: &FileNode) -> Result<()> {
+        let mut count = -1;
+        profile::read_i32(node, "count", &mut count)?;
+        if count < 0 {
+            return Ok(());
+        }
+        let mut list = Vec::new();
+        for n in 0..count {
+            let node = node.get(&format!("roi_{}", n))?;
+            if node.empty()? {
+                continue;
+            }
+            let (mut name, mut shape) = (format!("roi {}", n + 1), -1);
+            profile::read_string(&node, "name", &mut name)?;
+            profile::read_i32(&node, "shape", &mut shape)?;
+            let points = node.get("points")?.mat()?;
+            if points.empty() {
+                continue;
+            }
+            list.extend(Roi::from_points(name, shape as usize, points.data_typed()?));
+        }
+        for roi in &mut list {
+            roi.id = self.next_id();
+        }
+        self.count = self.count.max(list.len());
+        self.list = list;
+        self.readings.clear();
+        self.selected = None;
+        self.seeded = true;
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+use crate::*;
+use std::io::{BufWriter, Write};
+
+/// recording formats, index of `Recorder::format`
+pub const FORMATS: [&str; 5] = [
+    "MP4 (avc1, lossy)",
+    "MKV (FFV1, lossless)",
+    "PNG sequence",
+    "16-bit TIFF sequence",
+    "raw binary",
+];
+/// zlib level of the PNG sequences, low to keep up with the cameras
+const PNG_COMPRESSION: i32 = 1;
+/// start of a raw binary file, followed by the little endian u32 version, width, height,
+/// channels and bytes per sample, then the frames back to back in row order
+pub const RAW_MAGIC: &[u8; 8] = b"PPRAW\0\0\0";
+const RAW_VERSION: u32 = 1;
+
+/// where one stream of a recording goes, frame by frame
+pub trait Sink: Send {
+    /// `frame` is 8-bit BGR at the size the sink was opened with
+    fn write(&mut self, frame: &Mat) -> Result<(), String>;
+}
+
+pub struct Video(videoio::VideoWriter);
+
+/// images in a folder of their own, named by their row in the recording index
+pub struct Images {
+    folder: path::PathBuf,
+    extension: &'static str,
+    /// 8-bit frames are scaled by 257 to fill the 16-bit range
+    wide: bool,
+    count: u64,
+}
+
+pub struct Raw(BufWriter<fs::File>);
+
+/// opens a sink for the stream `name` in the given format, its files starting with `prefix`
+pub fn open(
+    format: usize,
+    prefix: &str,
+    name: &str,
+    fps: f64,
+    size: Size,
+) -> Result<Box<dyn Sink>, String> {
+    let video = |extension: &str, fourcc: [char; 4]| {
+        let path = format!("{}{}.{}", prefix, name, extension);
+        let [a, b, c, d] = fourcc;
+        let fourcc = videoio::VideoWriter::fourcc(a, b, c, d).unwrap();
+        match videoio::VideoWriter::new(&path, fourcc, fps, size, true) {
+            Ok(writer) if writer.is_opened().unwrap_or(false) => {
+                Ok(Box::new(Video(writer)) as Box<dyn Sink>)
+            }
+            Ok(_) => Err(format!("no {} encoder for {}", extension, path)),
+            Err(err) => Err(format!("could not record {}: {}", path, err.message)),
+        }
+    };
+    let images = |extension: &'static str, wide: bool| -> Result<Box<dyn Sink>, String> {
+        let folder = path::PathBuf::from(format!("{}{}", prefix, name));
+        fs::create_dir_all(&folder).map_err(|err| format!("{}: {}", folder.display(), err))?;
+        Ok(Box::new(Images {
+            folder,
+            extension,
+            wide,
+            count: 0,
+        }))
+    };
+    match format {
+        0 => video("mp4", ['a', 'v', 'c', '1']),
+        1 => video("mkv", ['F', 'F', 'V', '1']),
+        2 => images("png", false),
+        3 => images("tif", true),
+        _ => {
+            let path = format!("{}{}.raw", prefix, name);
+            let error = |err: std::io::Error| format!("{}: {}", path, err);
+            let mut file = BufWriter::new(fs::File::create(&path).map_err(error)?);
+            file.write_all(RAW_MAGIC).map_err(error)?;
+            for value in [RAW_VERSION, size.width as u32, size.height as u32, 3, 1] {
+                file.write_all(&value.to_le_bytes()).map_err(error)?;
+            }
+            Ok(Box::new(Raw(file)))
+        }
+    }
+}
+
+impl Sink for Video {
+    fn write(&mut self, frame: &Mat) -> Result<(), String> {
+        self.0.write(frame).map_err(|err| err.message)
+    }
+}
+
+impl Sink for Images {
+    fn write(&mut self, frame: &Mat) -> Result<(), String> {
+        let mut path = self.folder.clone();
+        path.push(format!("{:06}.{}", self.count, self.extension));
+        let path = path.to_string_lossy();
+        let written = if self.wide {
+            let mut wide = Mat::default();
+            frame
+                .convert_to(&mut wide, CV_16U, 257., 0.)
+                .map_err(|err| err.message)?;
+            imgcodecs::imwrite_def(&path, &wide)
+        } else {
+            let params = Vector::from_slice(&[imgcodecs::IMWRITE_PNG_COMPRESSION, PNG_COMPRESSION]);
+            imgcodecs::imwrite(&path, frame, &params)
+        };
+        // numbered from 0, as the index column of the recording
+        self.count += 1;
+        match written {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(format!("could not write {}", path)),
+            Err(err) => Err(err.message),
+        }
+    }
+}
+
+impl Sink for Raw {
+    fn write(&mut self, frame: &Mat) -> Result<(), String> {
+        let bytes = frame.data_bytes().map_err(|err| err.message)?;
+        self.0.write_all(bytes).map_err(|err| err.to_string())
+    }
+}
+
+impl Drop for Raw {
+    fn drop(&mut self) {
+        let _ = self.0.flush();
+    }
+}
@@ -0,0 +1,164 @@
+use crate::*;
+
+pub const COLUMNS: [&str; 10] = [
+    "mean",
+    "std dev",
+    "min",
+    "max",
+    "median",
+    "p5",
+    "p95",
+    "pixels",
+    "saturated",
+    "SNR",
+];
+pub const POLAR_COLUMNS: [&str; 4] = ["DoLP mean", "DoLP std dev", "AoLP mean", "AoLP circ std"];
+
+/// statistics of one 8-bit channel inside a mask
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ChannelStats {
+    pub mean: f64,
+    pub std: f64,
+    pub min: f64,
+    pub max: f64,
+    pub median: f64,
+    pub p5: f64,
+    pub p95: f64,
+    pub count: usize,
+    /// fraction of pixels at 255
+    pub saturated: f64,
+    /// mean / std dev
+    pub snr: f64,
+}
+
+impl ChannelStats {
+    pub fn from_histogram(hist: &[usize; 256]) -> Self {
+        let count: usize = hist.iter().sum();
+        if count == 0 {
+            return Self::default();
+        }
+        let n = count as f64;
+        let sum: f64 = hist.iter().enumerate().map(|(v, c)| (v * c) as f64).sum();
+        let mean = sum / n;
+        let var = hist
+            .iter()
+            .enumerate()
+            .map(|(v, c)| (v as f64 - mean).powi(2) * *c as f64)
+            .sum::<f64>()
+            / n;
+        let percentile = |q: f64| {
+            let target = (q * n).ceil().max(1.) as usize;
+            let mut cumulative = 0;
+            for (v, c) in hist.iter().enumerate() {
+                cumulative += c;
+                if cumulative >= target {
+                    return v as f64;
+                }
+            }
+            255.
+        };
+        let std = var.sqrt();
+        Self {
+            mean,
+            std,
+            min: hist.iter().position(|c| *c > 0).unwrap() as f64,
+            max: hist.iter().rposition(|c| *c > 0).unwrap() as f64,
+            median: percentile(0.5),
+            p5: percentile(0.05),
+            p95: percentile(0.95),
+            count,
+            saturated: hist[255] as f64 / n,
+            snr: if std > 0. { mean / std } else { f64::INFINITY },
+        }
+    }
+
+    /// values in the order of `COLUMNS`
+    pub fn values(&self) -> [f64; COLUMNS.len()] {
+        [
+            self.mean,
+            self.std,
+            self.min,
+            self.max,
+            self.median,
+            self.p5,
+            self.p95,
+            self.count as f64,
+            self.saturated,
+            self.snr,
+        ]
+    }
+}
+
+/// polarimetric statistics inside a mask, AoLP with circular statistics over its 180° period
+#[derive(Clone, Copy, Debug, Default)]
+pub struct PolarStats {
+    pub dolp_mean: f64,
+    pub dolp_std: f64,
+    /// degrees in [0, 180)
+    pub aolp_mean: f64,
+    /// degrees
+    pub aolp_std: f64,
+}
+
+impl PolarStats {
+    /// values in the order of `POLAR_COLUMNS`
+    pub fn values(&self) -> [f64; POLAR_COLUMNS.len()] {
+        [self.dolp_mean, self.dolp_std, self.aolp_mean, self.aolp_std]
+    }
+}
+
+/// statistics of one roi
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RoiStats {
+    /// per feed, per BGR channel
+    pub feeds: [[ChannelStats; 3]; 3],
+    pub polar: Option<PolarStats>,
+}
+
+/// per BGR channel statistics of an 8-bit 3 channel mat, `mask` has the same size
+pub fn channels(mat: &Mat, mask: &Mat) -> [ChannelStats; 3] {
+    let mut hist = [[0; 256]; 3];
+    for r in 0..mat.rows() {
+        let row = mat.at_row::<Vec3b>(r).unwrap();
+        let mask_row = mask.at_row::<u8>(r).unwrap();
+        for (px, m) in row.iter().zip(mask_row) {
+            if *m != 0 {
+                for c in 0..3 {
+                    hist[c][px[c] as usize] += 1;
+                }
+            }
+        }
+    }
+    hist.map(|h| ChannelStats::from_histogram(&h))
+}
+
+/// DoLP and AoLP statistics of CV_32F mats, `mask` has the same size
+pub fn polar(dolp: &Mat, aolp: &Mat, mask: &Mat) -> PolarStats {
+    let (mut n, mut sum, mut sum_sq, mut cos, mut sin) = (0., 0., 0., 0., 0.);
+    for r in 0..dolp.rows() {
+        let dolp_row = dolp.at_row::<f32>(r).unwrap();
+        let aolp_row = aolp.at_row::<f32>(r).unwrap();
+        let mask_row = mask.at_row::<u8>(r).unwrap();
+        for ((d, a), m) in dolp_row.iter().zip(aolp_row).zip(mask_row) {
+            if *m != 0 {
+                let (d, a) = (*d as f64, (2. * *a as f64).to_radians());
+                n += 1.;
+                sum += d;
+                sum_sq += d * d;
+                cos += a.cos();
+                sin += a.sin();
+            }
+        }
+    }
+    if n == 0. {
+        return PolarStats::default();
+    }
+    let dolp_mean = sum / n;
+    let resultant = (cos * cos + sin * sin).sqrt() / n;
+    PolarStats {
+        dolp_mean,
+        dolp_std: (sum_sq / n - dolp_mean * dolp_mean).max(0.).sqrt(),
+        aolp_mean: (0.5 * sin.atan2(cos).to_degrees()).rem_euclid(180.),
+        aolp_std: 0.5 * (-2. * resultant.max(1e-12).ln()).sqrt().to_degrees(),
+    }
+}
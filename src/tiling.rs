@@ -0,0 +1,138 @@
+use crate::*;
+use rayon::prelude::*;
+
+/// overlap above which boxes of one class from different tiles are merged
+const MERGE_NMS: f32 = 0.5;
+
+/// overlapping square tiles the frame is cut into, so small objects keep enough pixels
+/// at the network input
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tiling {
+    pub enabled: bool,
+    /// side of a tile in frame pixels
+    pub size: i32,
+    /// fraction of a tile shared with its neighbour
+    pub overlap: f32,
+}
+
+impl Default for Tiling {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            size: 640,
+            overlap: 0.2,
+        }
+    }
+}
+
+/// backends run side by side over the tiles, each one a copy of the model in memory
+pub fn threads() -> usize {
+    thread::available_parallelism()
+        .map_or(1, |n| n.get())
+        .min(4)
+}
+
+/// starts of the tiles along one side, the last one flush with the end
+fn offsets(length: i32, tile: i32, step: i32) -> Vec<i32> {
+    if length <= tile {
+        return vec![0];
+    }
+    let mut offsets: Vec<i32> = (0..length - tile).step_by(step as usize).collect();
+    offsets.push(length - tile);
+    offsets
+}
+
+/// the whole frame, for objects larger than a tile, followed by the tiles
+pub fn tiles(frame: Size, tiling: &Tiling) -> Vec<Rect> {
+    let tile = tiling.size.max(32);
+    let step = ((tile as f32 * (1. - tiling.overlap)) as i32).max(1);
+    let mut tiles = vec![Rect::new(0, 0, frame.width, frame.height)];
+    for y in offsets(frame.height, tile, step) {
+        for x in offsets(frame.width, tile, step) {
+            let size = Size::new(tile.min(frame.width), tile.min(frame.height));
+            tiles.push(Rect::from_point_size(Point::new(x, y), size));
+        }
+    }
+    tiles
+}
+
+/// a loaded model and the copies of it the tiles run on
+pub struct Models {
+    backends: Vec<Box<dyn Backend>>,
+    files: ModelFiles,
+    /// a copy did not load, the tiles stay on the backends that did
+    failed: bool,
+}
+
+impl Models {
+    pub fn new(backend: Box<dyn Backend>, files: ModelFiles) -> Self {
+        Self {
+            backends: vec![backend],
+            files,
+            failed: false,
+        }
+    }
+
+    /// copies of the model loaded from its files until there is one per tile thread
+    fn copies(&mut self) -> &mut [Box<dyn Backend>] {
+        while !self.failed && self.backends.len() < threads() {
+            match self.files.load() {
+                Ok((backend, _)) => self.backends.push(backend),
+                Err(_) => self.failed = true,
+            }
+        }
+        &mut self.backends
+    }
+}
+
+/// detections over the whole feed, tiled if the input asks for it and the feed is larger
+/// than a tile
+pub fn detect(models: &mut Models, feed: &Mat, input: &Input) -> Result<Detections, String> {
+    let error = |err: opencv::Error| format!("detection error: {}", err.message);
+    let size = feed.size().unwrap();
+    let tile = input.tiling.size.max(32);
+    if !input.tiling.enabled || (size.width <= tile && size.height <= tile) {
+        return models.backends[0].detect(feed, input).map_err(error);
+    }
+
+    let tiles = tiles(size, &input.tiling);
+    let backends = models.copies();
+    let count = backends.len();
+    let results: Vec<Result<Vec<Detections>>> = backends
+        .par_iter_mut()
+        .enumerate()
+        .map(|(n, backend)| {
+            tiles
+                .iter()
+                .skip(n)
+                .step_by(count)
+                .map(|tile| {
+                    let mut detections = backend.detect(&feed.roi(*tile)?.try_clone()?, input)?;
+                    let rects = detections.2.iter().map(|r| r + tile.tl()).collect();
+                    detections.2 = rects;
+                    Ok(detections)
+                })
+                .collect()
+        })
+        .collect();
+
+    let mut all = Detections::default();
+    for detections in results {
+        for (class_ids, scores, rects) in detections.map_err(error)? {
+            all.0.extend(class_ids);
+            all.1.extend(scores);
+            all.2.extend(rects);
+        }
+    }
+    let (class_ids, scores, rects) = &all;
+    let mut indices = Vector::<i32>::new();
+    dnn::nms_boxes_batched_def(rects, scores, class_ids, 0., MERGE_NMS, &mut indices)
+        .map_err(error)?;
+    let mut merged = Detections::default();
+    for i in indices {
+        merged.0.push(class_ids.get(i as usize).unwrap());
+        merged.1.push(scores.get(i as usize).unwrap());
+        merged.2.push(rects.get(i as usize).unwrap());
+    }
+    Ok(merged)
+}
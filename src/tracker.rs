@@ -0,0 +1,296 @@
+use crate::*;
+use opencv::video;
+use std::collections::VecDeque;
+use std::io::Write;
+
+/// detection results a track survives without a match
+const MAX_MISSES: u32 = 10;
+/// matches before a track is shown
+const MIN_HITS: u32 = 3;
+/// overlap below which a detection does not continue a track
+const MIN_IOU: f64 = 0.3;
+/// positions kept per track
+const HISTORY: usize = 1000;
+
+/// one detection after thresholds and non-maximum suppression
+#[derive(Clone, Copy, Debug)]
+pub struct Detection {
+    pub class: ClassID,
+    pub score: Score,
+    pub rect: Rect,
+}
+
+/// an object followed through the detection results, its box smoothed by a constant velocity
+/// Kalman filter over center, width and height
+pub struct Track {
+    pub id: u64,
+    pub class: ClassID,
+    pub score: Score,
+    pub rect: Rect,
+    pub hits: u32,
+    /// consecutive results without a match
+    pub misses: u32,
+    /// (frame, smoothed box)
+    pub history: VecDeque<(u64, Rect)>,
+    kalman: video::KalmanFilter,
+}
+
+/// `values.len()` x `cols` matrix with `values` on its diagonal
+fn diagonal(values: &[f32], cols: usize) -> Mat {
+    let rows: Vec<Vec<f32>> = (0..values.len())
+        .map(|r| {
+            (0..cols)
+                .map(|c| if r == c { values[r] } else { 0. })
+                .collect()
+        })
+        .collect();
+    Mat::from_slice_2d(&rows).unwrap()
+}
+
+/// column vector
+fn column(values: &[f32]) -> Mat {
+    let rows: Vec<[f32; 1]> = values.iter().map(|v| [*v]).collect();
+    Mat::from_slice_2d(&rows).unwrap()
+}
+
+fn iou(a: Rect, b: Rect) -> f64 {
+    let overlap = (a & b).area() as f64;
+    let union = (a.area() + b.area()) as f64 - overlap;
+    if union > 0. {
+        overlap / union
+    } else {
+        0.
+    }
+}
+
+/// center, width and height
+fn measurement(rect: Rect) -> [f32; 4] {
+    let (w, h) = (rect.width as f32, rect.height as f32);
+    [rect.x as f32 + w / 2., rect.y as f32 + h / 2., w, h]
+}
+
+/// box from the first four entries of a state vector
+fn to_rect(state: &Mat) -> Rect {
+    let v = |i: i32| *state.at::<f32>(i).unwrap();
+    let (w, h) = (v(2).max(1.), v(3).max(1.));
+    Rect::new(
+        (v(0) - w / 2.).round() as i32,
+        (v(1) - h / 2.).round() as i32,
+        w.round() as i32,
+        h.round() as i32,
+    )
+}
+
+impl Track {
+    fn new(id: u64, detection: &Detection) -> Self {
+        let mut kalman = video::KalmanFilter::new(8, 4, 0, CV_32F).unwrap();
+        let transition: Vec<Vec<f32>> = (0..8)
+            .map(|r| {
+                (0..8)
+                    .map(|c| (r == c || c == r + 4) as i32 as f32)
+                    .collect()
+            })
+            .collect();
+        kalman.set_transition_matrix(Mat::from_slice_2d(&transition).unwrap());
+        kalman.set_measurement_matrix(diagonal(&[1.; 4], 8));
+        kalman.set_process_noise_cov(diagonal(&[1., 1., 1., 1., 0.01, 0.01, 0.01, 0.01], 8));
+        kalman.set_measurement_noise_cov(diagonal(&[4.; 4], 4));
+        kalman.set_error_cov_post(diagonal(&[10., 10., 10., 10., 1e3, 1e3, 1e3, 1e3], 8));
+        let [cx, cy, w, h] = measurement(detection.rect);
+        kalman.set_state_post(column(&[cx, cy, w, h, 0., 0., 0., 0.]));
+        Self {
+            id,
+            class: detection.class,
+            score: detection.score,
+            rect: detection.rect,
+            hits: 1,
+            misses: 0,
+            history: VecDeque::new(),
+            kalman,
+        }
+    }
+
+    fn predict(&mut self) {
+        let state = self.kalman.predict_def().unwrap();
+        self.rect = to_rect(&state);
+    }
+
+    fn correct(&mut self, detection: &Detection) {
+        let state = self
+            .kalman
+            .correct(&column(&measurement(detection.rect)))
+            .unwrap();
+        self.rect = to_rect(&state);
+        self.score = detection.score;
+        self.hits += 1;
+        self.misses = 0;
+    }
+
+    pub fn confirmed(&self) -> bool {
+        self.hits >= MIN_HITS
+    }
+
+    pub fn center(&self) -> Point {
+        Point::new(
+            self.rect.x + self.rect.width / 2,
+            self.rect.y + self.rect.height / 2,
+        )
+    }
+}
+
+/// IoU matching of detections to Kalman predicted tracks, SORT-style
+#[derive(Default)]
+pub struct Tracker {
+    pub enabled: bool,
+    pub tracks: Vec<Track>,
+    /// draws the history of every confirmed track
+    pub trails: bool,
+    next_id: u64,
+    status: String,
+}
+
+impl Tracker {
+    /// advances the tracks to a new detection result taken at `frame`
+    pub fn update(&mut self, frame: u64, detections: &[Detection]) {
+        for track in &mut self.tracks {
+            track.predict();
+        }
+
+        let mut pairs: Vec<(f64, usize, usize)> = Vec::new();
+        for (t, track) in self.tracks.iter().enumerate() {
+            for (d, detection) in detections.iter().enumerate() {
+                let overlap = iou(track.rect, detection.rect);
+                if track.class == detection.class && overlap >= MIN_IOU {
+                    pairs.push((overlap, t, d));
+                }
+            }
+        }
+        pairs.sort_by(|a, b| b.0.total_cmp(&a.0));
+        let mut matched_tracks = vec![false; self.tracks.len()];
+        let mut matched_detections = vec![false; detections.len()];
+        for (_, t, d) in pairs {
+            if !matched_tracks[t] && !matched_detections[d] {
+                matched_tracks[t] = true;
+                matched_detections[d] = true;
+                self.tracks[t].correct(&detections[d]);
+            }
+        }
+
+        for (track, matched) in self.tracks.iter_mut().zip(&matched_tracks) {
+            if !matched {
+                track.misses += 1;
+            }
+        }
+        self.tracks.retain(|track| track.misses <= MAX_MISSES);
+        for (detection, _) in detections
+            .iter()
+            .zip(&matched_detections)
+            .filter(|(_, matched)| !**matched)
+        {
+            self.next_id += 1;
+            self.tracks.push(Track::new(self.next_id, detection));
+        }
+
+        for track in &mut self.tracks {
+            track.history.push_back((frame, track.rect));
+            if track.history.len() > HISTORY {
+                track.history.pop_front();
+            }
+        }
+    }
+
+    /// confirmed tracks of the shown classes in their class colour, gray while unmatched
+    pub fn draw(&self, mat: &mut Mat, classes: &Classes, class_filter: &class_filter::ClassFilter) {
+        for track in self.tracks.iter().filter(|t| t.confirmed()) {
+            let style = class_filter.style(track.class);
+            if !style.shown {
+                continue;
+            }
+            let color = if track.misses > 0 {
+                Scalar::all(128.)
+            } else {
+                style.scalar()
+            };
+            imgproc::rectangle_def(mat, track.rect, color).unwrap();
+            let label = format!(
+                "#{} {} {:.2}",
+                track.id,
+                detection::label(classes, track.class),
+                track.score
+            );
+            let org = Point::new(track.rect.x, track.rect.y - 4);
+            let font = imgproc::FONT_HERSHEY_SIMPLEX;
+            imgproc::put_text_def(mat, &label, org, font, 0.5, color).unwrap();
+            if self.trails && track.history.len() > 1 {
+                let points: Vector<Point> = track
+                    .history
+                    .iter()
+                    .map(|(_, r)| Point::new(r.x + r.width / 2, r.y + r.height / 2))
+                    .collect();
+                let polygons = Vector::<Vector<Point>>::from_iter([points]);
+                imgproc::polylines_def(mat, &polygons, false, color).unwrap();
+            }
+        }
+    }
+
+    pub fn panel(&mut self, ui: &imgui::Ui, classes: &Classes) {
+        ui.checkbox("track objects", &mut self.enabled);
+        if !self.enabled {
+            return;
+        }
+        ui.same_line();
+        ui.checkbox("trails", &mut self.trails);
+        ui.same_line();
+        if ui.button("export tracks") {
+            self.status = match self.export(classes) {
+                Ok(path) => format!("saved {}", path),
+                Err(err) => format!("export error: {}", err),
+            };
+        }
+        ui.same_line();
+        if ui.button("reset tracks") {
+            self.tracks.clear();
+        }
+        if !self.status.is_empty() {
+            ui.text(&self.status);
+        }
+        if let Some(_) = ui.begin_table("tracks", 5) {
+            for header in ["id", "class", "score", "center", "frames"] {
+                ui.table_setup_column(header);
+            }
+            ui.table_headers_row();
+            for track in self.tracks.iter().filter(|t| t.confirmed()) {
+                let center = track.center();
+                ui.table_next_column();
+                ui.text(track.id.to_string());
+                ui.table_next_column();
+                ui.text(detection::label(classes, track.class));
+                ui.table_next_column();
+                ui.text(format!("{:.2}", track.score));
+                ui.table_next_column();
+                ui.text(format!("{}, {}", center.x, center.y));
+                ui.table_next_column();
+                ui.text(track.history.len().to_string());
+            }
+        }
+    }
+
+    /// history of every confirmed track, one row per frame
+    fn export(&self, classes: &Classes) -> std::io::Result<String> {
+        let path = utils::get_save_filepath("tracks.csv");
+        let mut writer = std::io::BufWriter::new(fs::File::create(&path)?);
+        writeln!(writer, "track,class,frame,x,y,width,height")?;
+        for track in self.tracks.iter().filter(|t| t.confirmed()) {
+            let class = utils::csv_field(&detection::label(classes, track.class));
+            for (frame, r) in &track.history {
+                writeln!(
+                    writer,
+                    "{},{},{},{},{},{},{}",
+                    track.id, class, frame, r.x, r.y, r.width, r.height
+                )?;
+            }
+        }
+        writer.flush()?;
+        Ok(path)
+    }
+}
@@ -30,3 +30,44 @@ pub fn get_save_filepath(name: &str) -> String {
     filepath.push(format!("{}-{}", i + 1, name));
     filepath.to_str().unwrap().to_string()
 }
+
+/// quoted and escaped JSON string
+pub fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// JSON number, null if not finite
+pub fn json_number(v: f64) -> String {
+    match v.is_finite() {
+        true => format!("{}", v),
+        false => "null".into(),
+    }
+}
+
+/// CSV field, quoted if needed
+pub fn csv_field(s: &str) -> String {
+    match s.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", s.replace('"', "\"\"")),
+        false => s.into(),
+    }
+}
+
+/// seconds since the unix epoch
+pub fn timestamp() -> f64 {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs_f64()
+}
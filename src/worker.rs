@@ -0,0 +1,93 @@
+use crate::*;
+use mpsc::{Receiver, TryRecvError};
+use std::sync::{Arc, Condvar, Mutex};
+
+struct Slot<In> {
+    /// only the latest input is kept, an older one still waiting is dropped
+    pending: Option<In>,
+    stop: bool,
+}
+
+/// runs `work` on the latest submitted input in a background thread.
+/// The thread sleeps while there is nothing to do, and stops when the worker is dropped.
+pub struct Worker<In, Out> {
+    slot: Arc<(Mutex<Slot<In>>, Condvar)>,
+    results: Receiver<Result<Out, String>>,
+    thread: Option<thread::JoinHandle<()>>,
+    /// inputs replaced before the thread got to them
+    pub dropped: u64,
+}
+
+impl<In: Send + 'static, Out: Send + 'static> Worker<In, Out> {
+    pub fn spawn(mut work: impl FnMut(In) -> Result<Out, String> + Send + 'static) -> Self {
+        let slot = Arc::new((
+            Mutex::new(Slot {
+                pending: None,
+                stop: false,
+            }),
+            Condvar::new(),
+        ));
+        let (sender, results) = mpsc::channel();
+        let thread_slot = slot.clone();
+        let thread = thread::spawn(move || {
+            let (lock, ready) = &*thread_slot;
+            loop {
+                let input = {
+                    let slot = lock.lock().unwrap();
+                    let mut slot = ready
+                        .wait_while(slot, |s| s.pending.is_none() && !s.stop)
+                        .unwrap();
+                    if slot.stop {
+                        return;
+                    }
+                    slot.pending.take().unwrap()
+                };
+                if sender.send(work(input)).is_err() {
+                    return;
+                }
+            }
+        });
+        Self {
+            slot,
+            results,
+            thread: Some(thread),
+            dropped: 0,
+        }
+    }
+
+    /// hands the input to the thread, replacing the one it has not started on yet
+    pub fn submit(&mut self, input: In) {
+        let (lock, ready) = &*self.slot;
+        if lock.lock().unwrap().pending.replace(input).is_some() {
+            self.dropped += 1;
+        }
+        ready.notify_one();
+    }
+
+    /// newest result since the last call, an error once the thread has stopped
+    pub fn latest(&mut self) -> Option<Result<Out, String>> {
+        let mut latest = None;
+        loop {
+            match self.results.try_recv() {
+                Ok(result) => latest = Some(result),
+                Err(TryRecvError::Empty) => return latest,
+                Err(TryRecvError::Disconnected) => {
+                    return latest.or_else(|| Some(Err("the worker thread has stopped".into())))
+                }
+            }
+        }
+    }
+}
+
+impl<In, Out> Drop for Worker<In, Out> {
+    fn drop(&mut self) {
+        let (lock, ready) = &*self.slot;
+        if let Ok(mut slot) = lock.lock() {
+            slot.stop = true;
+        }
+        ready.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}